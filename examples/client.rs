@@ -1,6 +1,8 @@
-use paymail_rs::{PaymailClient, models::PaymentRequest};
+use paymail_rs::{
+    PaymailClient,
+    models::{P2PTxMetadata, PaymentRequest},
+};
 use secp256k1::SecretKey;
-use serde_json::json;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -28,8 +30,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
     println!("P2P: {p2p_resp:?}");
 
+    let metadata = P2PTxMetadata {
+        sender: Some("sender@wallet.com".to_string()),
+        ..Default::default()
+    };
     let tx_resp = client
-        .send_p2p_tx("alice@wallet.com", "txhex", json!({}), "ref")
+        .send_p2p_tx("alice@wallet.com", "01000000000000000000", metadata, "ref")
         .await?;
     println!("Tx: {tx_resp:?}");
 
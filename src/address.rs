@@ -0,0 +1,145 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::PaymailError;
+
+/// A validated `alias@domain` paymail address.
+///
+/// The alias may contain ASCII letters, digits, `.`, `_` and `-`. The domain
+/// is lowercased and IDNA-normalised to its ASCII (punycode) form, a trailing
+/// dot is dropped, and IP literals are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PaymailAddress {
+    alias: String,
+    domain: String,
+}
+
+impl PaymailAddress {
+    pub fn new(alias: &str, domain: &str) -> Result<Self, PaymailError> {
+        Ok(Self {
+            alias: validate_alias(alias)?,
+            domain: normalize_domain(domain)?,
+        })
+    }
+
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+}
+
+fn validate_alias(alias: &str) -> Result<String, PaymailError> {
+    if alias.is_empty() {
+        return Err(PaymailError::InvalidFormat(
+            "Empty paymail alias".to_string(),
+        ));
+    }
+    if let Some(c) = alias
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        return Err(PaymailError::InvalidFormat(format!(
+            "Invalid character {c:?} in paymail alias"
+        )));
+    }
+    Ok(alias.to_string())
+}
+
+/// Normalises a paymail domain the way `PaymailAddress` does, so that
+/// domain-keyed lookups agree with address-based ones.
+pub fn normalize_domain(domain: &str) -> Result<String, PaymailError> {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    if domain.starts_with('[') || domain.parse::<IpAddr>().is_ok() {
+        return Err(PaymailError::InvalidFormat(format!(
+            "IP literal {domain} is not a paymail domain"
+        )));
+    }
+    let ascii = idna::domain_to_ascii_strict(domain)
+        .map_err(|_| PaymailError::InvalidFormat(format!("Invalid paymail domain {domain}")))?;
+    // No top-level domain is numeric, so this also catches shorthand IPv4
+    // forms such as `127.1`.
+    let numeric_tld = ascii
+        .rsplit('.')
+        .next()
+        .is_some_and(|tld| tld.bytes().all(|b| b.is_ascii_digit()));
+    if ascii.is_empty() || numeric_tld {
+        return Err(PaymailError::InvalidFormat(format!(
+            "Invalid paymail domain {domain}"
+        )));
+    }
+    Ok(ascii)
+}
+
+impl FromStr for PaymailAddress {
+    type Err = PaymailError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (alias, domain) = s
+            .split_once('@')
+            .ok_or_else(|| PaymailError::InvalidFormat(s.to_string()))?;
+        Self::new(alias, domain)
+    }
+}
+
+impl fmt::Display for PaymailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.alias, self.domain)
+    }
+}
+
+impl TryFrom<String> for PaymailAddress {
+    type Error = PaymailError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PaymailAddress> for String {
+    fn from(address: PaymailAddress) -> Self {
+        address.to_string()
+    }
+}
+
+/// Anything `PaymailClient` accepts as a paymail: a `PaymailAddress` or a
+/// string that parses as one.
+pub trait IntoPaymailAddress {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError>;
+}
+
+impl IntoPaymailAddress for PaymailAddress {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError> {
+        Ok(self)
+    }
+}
+
+impl IntoPaymailAddress for &PaymailAddress {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoPaymailAddress for &str {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError> {
+        self.parse()
+    }
+}
+
+impl IntoPaymailAddress for String {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError> {
+        self.parse()
+    }
+}
+
+impl IntoPaymailAddress for &String {
+    fn into_paymail_address(self) -> Result<PaymailAddress, PaymailError> {
+        self.parse()
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::PaymailError;
+use crate::models::Capabilities;
+use crate::transport::HttpResponse;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedCapabilities {
+    pub capabilities: Capabilities,
+    /// Scheme, host and port the document was fetched from; relative
+    /// endpoint templates are resolved against it.
+    #[serde(default)]
+    pub base_url: String,
+    pub fetched_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+impl CachedCapabilities {
+    pub fn is_fresh(&self) -> bool {
+        Utc::now() < self.expires_at
+    }
+}
+
+/// Derives capability document lifetimes from `Cache-Control` and `Expires`,
+/// clamped to `[min_ttl, max_ttl]`; `default_ttl` applies when neither is sent.
+#[derive(Debug, Clone, Copy)]
+pub struct CachePolicy {
+    pub default_ttl: Duration,
+    pub min_ttl: Duration,
+    pub max_ttl: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            default_ttl: Duration::from_secs(3600),
+            min_ttl: Duration::from_secs(60),
+            max_ttl: Duration::from_secs(86400),
+        }
+    }
+}
+
+impl CachePolicy {
+    /// Returns `None` when the response must not be stored (`no-store`).
+    pub fn ttl_for(&self, resp: &HttpResponse) -> Option<Duration> {
+        let mut max_age = None;
+        let mut no_cache = false;
+        if let Some(cache_control) = resp.header("cache-control") {
+            for directive in cache_control.split(',') {
+                let directive = directive.trim().to_ascii_lowercase();
+                if directive == "no-store" {
+                    return None;
+                } else if directive == "no-cache" {
+                    no_cache = true;
+                } else if let Some(secs) = directive
+                    .strip_prefix("max-age=")
+                    .and_then(|secs| secs.trim_matches('"').parse::<u64>().ok())
+                {
+                    max_age = Some(Duration::from_secs(secs));
+                }
+            }
+        }
+        if no_cache {
+            max_age = Some(Duration::ZERO);
+        }
+        let ttl = max_age
+            .or_else(|| expires_ttl(resp))
+            .unwrap_or(self.default_ttl);
+        Some(ttl.clamp(self.min_ttl, self.max_ttl.max(self.min_ttl)))
+    }
+}
+
+fn expires_ttl(resp: &HttpResponse) -> Option<Duration> {
+    let expires = DateTime::parse_from_rfc2822(resp.header("expires")?.trim()).ok();
+    let Some(expires) = expires else {
+        // Invalid dates (e.g. "0") mean "already expired".
+        return Some(Duration::ZERO);
+    };
+    let now = resp
+        .header("date")
+        .and_then(|d| DateTime::parse_from_rfc2822(d.trim()).ok())
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+    Some(
+        (expires.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[async_trait::async_trait]
+pub trait CapabilityStore {
+    async fn get(&self, domain: &str) -> Result<Option<CachedCapabilities>, PaymailError>;
+    async fn put(&self, domain: &str, entry: CachedCapabilities) -> Result<(), PaymailError>;
+    async fn remove(&self, domain: &str) -> Result<(), PaymailError>;
+}
+
+#[derive(Default)]
+pub struct MemoryCapabilityStore {
+    entries: RwLock<HashMap<String, CachedCapabilities>>,
+}
+
+#[async_trait::async_trait]
+impl CapabilityStore for MemoryCapabilityStore {
+    async fn get(&self, domain: &str) -> Result<Option<CachedCapabilities>, PaymailError> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        Ok(entries.get(domain).cloned())
+    }
+
+    async fn put(&self, domain: &str, entry: CachedCapabilities) -> Result<(), PaymailError> {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.insert(domain.to_string(), entry);
+        Ok(())
+    }
+
+    async fn remove(&self, domain: &str) -> Result<(), PaymailError> {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.remove(domain);
+        Ok(())
+    }
+}
+
+/// Stores one JSON document per domain in a directory, so entries written by
+/// one process are picked up by the next.
+pub struct FileCapabilityStore {
+    dir: PathBuf,
+}
+
+impl FileCapabilityStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path_for(&self, domain: &str) -> PathBuf {
+        let domain = domain.to_ascii_lowercase();
+        let safe = !domain.is_empty()
+            && !domain.starts_with('.')
+            && domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+        let name = if safe { domain } else { hex::encode(domain) };
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+#[async_trait::async_trait]
+impl CapabilityStore for FileCapabilityStore {
+    async fn get(&self, domain: &str) -> Result<Option<CachedCapabilities>, PaymailError> {
+        match tokio::fs::read(self.path_for(domain)).await {
+            // A corrupt or foreign file is treated as a miss and overwritten later.
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put(&self, domain: &str, entry: CachedCapabilities) -> Result<(), PaymailError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path_for(domain);
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        tokio::fs::write(&tmp, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, domain: &str) -> Result<(), PaymailError> {
+        match tokio::fs::remove_file(self.path_for(domain)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
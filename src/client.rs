@@ -1,28 +1,321 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
 
-use async_mutex::Mutex;
+use chrono::TimeDelta;
 use chrono::prelude::*;
-use reqwest::Client;
-use secp256k1::SecretKey;
+use reqwest::{Client, Url, redirect};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use tokio::sync::watch;
 use tokio::time::{Duration, Instant};
 
-use crate::errors::PaymailError;
+use crate::address::{self, IntoPaymailAddress};
+use crate::cache::{CachePolicy, CachedCapabilities, CapabilityStore, MemoryCapabilityStore};
+use crate::errors::{HttpErrorDetails, PaymailError, TimeoutPhase};
 use crate::models::{
-    Capabilities, P2PPaymentDestinationRequest, P2PPaymentDestinationResponse, P2PTxRequest,
-    P2PTxResponse, PaymentDestinationResponse, PaymentRequest, PkiResponse,
+    Brfc, Capabilities, P2PPaymentDestinationRequest, P2PPaymentDestinationResponse, P2PTxMetadata,
+    P2PTxRequest, P2PTxResponse, PaymentDestinationResponse, PaymentRequest, PkiResponse,
+    UnknownFieldPolicy,
 };
 use crate::resolver::Resolver;
-use crate::utils;
+use crate::retry::RetryPolicy;
+use crate::transport::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, HttpRequest, HttpResponse, HttpTransport,
+    ReqwestTransport,
+};
+use crate::utils::{self, SignatureScheme};
 
 #[derive(Clone)]
 pub struct PaymailClient {
-    http: Arc<Client>,
-    cache: Arc<Mutex<HashMap<String, (Capabilities, Instant)>>>,
-    cache_ttl: Duration,
+    http: Arc<dyn HttpTransport + Send + Sync>,
+    cache: Arc<CapabilityCache>,
+    cache_policy: CachePolicy,
     priv_key: SecretKey,
+    signature_scheme: SignatureScheme,
+    field_policy: UnknownFieldPolicy,
     resolver: Arc<dyn Resolver + Send + Sync>,
+    scheme_policy: SchemePolicy,
+    retry_policy: RetryPolicy,
+    request_timeout: Option<Duration>,
+    deadline: Option<Duration>,
+    max_staleness: Option<Duration>,
+}
+
+/// When an in-memory cache entry lapses; `None` never expires.
+type Expiry = Option<Instant>;
+
+/// Same limit as reqwest's default redirect policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// How long a domain whose background refresh failed keeps serving its stale
+/// entry before another refresh is attempted.
+const REFRESH_BACKOFF: Duration = Duration::from_secs(30);
+
+struct CapabilityCache {
+    store: Arc<dyn CapabilityStore + Send + Sync>,
+    negative: StdMutex<HashMap<String, (NegativeEntry, Expiry)>>,
+    negative_ttl: Duration,
+    hosts: StdMutex<HashMap<String, (Vec<String>, Expiry)>>,
+    refresh_backoff: StdMutex<HashMap<String, Expiry>>,
+    inflight: StdMutex<HashMap<String, watch::Receiver<Option<FlightResult>>>>,
+}
+
+/// Outcome of a capability fetch, as handed to the lookups that waited on it.
+type FlightResult = Result<CachedCapabilities, PaymailError>;
+
+enum Flight {
+    /// This lookup fetches and must publish the result via `finish_flight`.
+    Leader(watch::Sender<Option<FlightResult>>),
+    /// Another lookup is fetching; its result arrives here, or the channel
+    /// closes if that lookup was dropped before finishing.
+    Follower(watch::Receiver<Option<FlightResult>>),
+}
+
+/// Failures that are answers in their own right. Transient ones such as
+/// `DnsUnavailable` or timeouts are never cached.
+#[derive(Clone)]
+enum NegativeEntry {
+    NoHost(String),
+    NotFound(Box<HttpErrorDetails>),
+    InvalidDocument(String),
+}
+
+impl NegativeEntry {
+    fn from_error(err: &PaymailError) -> Option<Self> {
+        match err {
+            PaymailError::DnsFailure(msg) => Some(NegativeEntry::NoHost(msg.clone())),
+            PaymailError::NotFound(details) => Some(NegativeEntry::NotFound(details.clone())),
+            PaymailError::InvalidCapabilities(msg) => {
+                Some(NegativeEntry::InvalidDocument(msg.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_error(&self) -> PaymailError {
+        match self {
+            NegativeEntry::NoHost(msg) => PaymailError::DnsFailure(msg.clone()),
+            NegativeEntry::NotFound(details) => PaymailError::NotFound(details.clone()),
+            NegativeEntry::InvalidDocument(msg) => PaymailError::InvalidCapabilities(msg.clone()),
+        }
+    }
+}
+
+impl CapabilityCache {
+    fn new(store: Arc<dyn CapabilityStore + Send + Sync>, negative_ttl: Duration) -> Self {
+        Self {
+            store,
+            negative: StdMutex::new(HashMap::new()),
+            negative_ttl,
+            hosts: StdMutex::new(HashMap::new()),
+            refresh_backoff: StdMutex::new(HashMap::new()),
+            inflight: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_negative(&self, domain: &str) -> Option<PaymailError> {
+        let negative = self.negative.lock().unwrap_or_else(|e| e.into_inner());
+        match negative.get(domain) {
+            Some((entry, exp)) if !is_expired(*exp) => Some(entry.to_error()),
+            _ => None,
+        }
+    }
+
+    fn record_failure(&self, domain: &str, err: &PaymailError) {
+        if self.negative_ttl.is_zero() {
+            return;
+        }
+        if let Some(entry) = NegativeEntry::from_error(err) {
+            let mut negative = self.negative.lock().unwrap_or_else(|e| e.into_inner());
+            negative.insert(
+                domain.to_string(),
+                (entry, instant_after(self.negative_ttl)),
+            );
+        }
+    }
+
+    fn get_hosts(&self, domain: &str) -> Option<Vec<String>> {
+        let hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        match hosts.get(domain) {
+            Some((base_urls, exp)) if !is_expired(*exp) => Some(base_urls.clone()),
+            _ => None,
+        }
+    }
+
+    fn put_hosts(&self, domain: &str, base_urls: Vec<String>, ttl: Duration) {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        hosts.insert(domain.to_string(), (base_urls, instant_after(ttl)));
+    }
+
+    fn refresh_due(&self, domain: &str) -> bool {
+        let backoff = self
+            .refresh_backoff
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        backoff.get(domain).is_none_or(|exp| is_expired(*exp))
+    }
+
+    fn record_refresh_failure(&self, domain: &str) {
+        let mut backoff = self
+            .refresh_backoff
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        backoff.insert(domain.to_string(), instant_after(REFRESH_BACKOFF));
+    }
+
+    async fn purge(&self, domain: &str) -> Result<(), PaymailError> {
+        self.negative
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+        self.hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+        self.refresh_backoff
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+        self.store.remove(domain).await
+    }
+
+    // Store failures degrade to a cache miss rather than failing the lookup.
+    async fn get(&self, domain: &str) -> Option<CachedCapabilities> {
+        self.store.get(domain).await.ok().flatten()
+    }
+
+    async fn get_fresh(&self, domain: &str) -> Option<CachedCapabilities> {
+        self.get(domain).await.filter(|entry| entry.is_fresh())
+    }
+
+    async fn put(&self, domain: &str, mut entry: CachedCapabilities, ttl: Option<Duration>) {
+        let Some(ttl) = ttl else {
+            let _ = self.store.remove(domain).await;
+            return;
+        };
+        self.negative
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+        self.refresh_backoff
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+        entry.fetched_at = Utc::now();
+        entry.expires_at = utc_after(entry.fetched_at, ttl);
+        let _ = self.store.put(domain, entry).await;
+    }
+
+    /// Joins the fetch in flight for `domain`, or starts one, so that
+    /// concurrent lookups of one domain share a single fetch and its result
+    /// while other domains proceed independently.
+    fn join_flight(&self, domain: &str) -> Flight {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rx) = inflight.get(domain) {
+            // A closed channel means the leader was dropped mid-fetch.
+            if rx.has_changed().is_ok() {
+                return Flight::Follower(rx.clone());
+            }
+        }
+        let (tx, rx) = watch::channel(None);
+        inflight.insert(domain.to_string(), rx);
+        Flight::Leader(tx)
+    }
+
+    fn finish_flight(
+        &self,
+        domain: &str,
+        tx: watch::Sender<Option<FlightResult>>,
+        result: &FlightResult,
+    ) {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if inflight
+            .get(domain)
+            .is_some_and(|rx| rx.same_channel(&tx.subscribe()))
+        {
+            inflight.remove(domain);
+        }
+        drop(inflight);
+        tx.send_replace(Some(share_result(result)));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Whether waiting `delay` still leaves time before the deadline.
+    fn allows(self, delay: Duration) -> bool {
+        self.0.is_none_or(|at| {
+            Instant::now()
+                .checked_add(delay)
+                .is_some_and(|resume| resume < at)
+        })
+    }
+
+    async fn run<T>(
+        self,
+        phase: TimeoutPhase,
+        fut: impl Future<Output = Result<T, PaymailError>>,
+    ) -> Result<T, PaymailError> {
+        let result = match self.0 {
+            Some(at) => tokio::time::timeout_at(at, fut)
+                .await
+                .map_err(|_| PaymailError::Timeout(phase))?,
+            None => fut.await,
+        };
+        result.map_err(|err| match err {
+            PaymailError::Timeout(TimeoutPhase::Connect) => err,
+            PaymailError::Timeout(_) => PaymailError::Timeout(phase),
+            err => err,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SchemePolicy {
+    plaintext_hosts: HashSet<String>,
+}
+
+impl SchemePolicy {
+    pub fn https_only() -> Self {
+        Self::default()
+    }
+
+    /// IPv6 hosts may be given with or without brackets.
+    pub fn allow_plaintext(mut self, host: &str) -> Self {
+        self.plaintext_hosts.insert(plain_host(host));
+        self
+    }
+
+    pub fn is_plaintext_allowed(&self, host: &str) -> bool {
+        self.plaintext_hosts.contains(&plain_host(host))
+    }
+
+    pub fn scheme_for(&self, host: &str) -> &'static str {
+        if self.is_plaintext_allowed(host) {
+            "http"
+        } else {
+            "https"
+        }
+    }
+
+    pub fn check_url(&self, url: &str) -> Result<(), PaymailError> {
+        let parsed = Url::parse(url).map_err(|e| PaymailError::InvalidFormat(e.to_string()))?;
+        match parsed.scheme() {
+            "https" => Ok(()),
+            "http"
+                if parsed
+                    .host_str()
+                    .is_some_and(|h| self.is_plaintext_allowed(h)) =>
+            {
+                Ok(())
+            }
+            _ => Err(PaymailError::InsecureScheme(url.to_string())),
+        }
+    }
 }
 
 impl PaymailClient {
@@ -31,172 +324,544 @@ impl PaymailClient {
     }
 
     pub async fn get_base_url(&self, domain: &str) -> Result<String, PaymailError> {
-        let (host, port) = self.resolver.resolve_host(domain).await?;
-        Ok(format!("http://{host}:{port}"))
+        let domain = address::normalize_domain(domain)?;
+        let mut base_urls = self.base_urls(&domain).await?;
+        Ok(base_urls.remove(0))
+    }
+
+    /// Candidate base URLs for `domain`, in the order they should be tried.
+    /// The order is kept for as long as the answer is cached, so a weighted
+    /// SRV draw spreads load across clients rather than across calls.
+    async fn base_urls(&self, domain: &str) -> Result<Vec<String>, PaymailError> {
+        if let Some(base_urls) = self.cache.get_hosts(domain) {
+            return Ok(base_urls);
+        }
+        let endpoint = self.resolver.resolve(domain).await?;
+        let base_urls: Vec<String> = endpoint
+            .candidates
+            .into_iter()
+            .map(|(host, port)| {
+                let scheme = self.scheme_policy.scheme_for(&host);
+                if host.contains(':') {
+                    format!("{scheme}://[{host}]:{port}")
+                } else {
+                    format!("{scheme}://{host}:{port}")
+                }
+            })
+            .collect();
+        if base_urls.is_empty() {
+            return Err(PaymailError::DnsFailure(format!(
+                "No host found for {domain}"
+            )));
+        }
+        let ttl = endpoint.ttl.unwrap_or(self.cache_policy.default_ttl);
+        self.cache.put_hosts(domain, base_urls.clone(), ttl);
+        Ok(base_urls)
+    }
+
+    fn endpoint_url(&self, base_url: &str, endpoint: String) -> Result<String, PaymailError> {
+        let url = if endpoint.starts_with('/') {
+            format!("{base_url}{endpoint}")
+        } else {
+            endpoint
+        };
+        self.scheme_policy.check_url(&url)?;
+        Ok(url)
+    }
+
+    fn start_deadline(&self) -> Deadline {
+        // A deadline too far out to represent is no deadline at all.
+        Deadline(self.deadline.and_then(instant_after))
     }
 
     pub async fn get_capabilities(&self, domain: &str) -> Result<Capabilities, PaymailError> {
-        let mut cache = self.cache.lock().await;
-        #[allow(clippy::collapsible_if)]
-        if let Some((caps, exp)) = cache.get(domain) {
-            if Instant::now() < *exp {
-                return Ok(caps.clone());
+        let domain = address::normalize_domain(domain)?;
+        let entry = self
+            .capabilities_within(&domain, self.start_deadline())
+            .await?;
+        Ok(entry.capabilities)
+    }
+
+    async fn capabilities_within(
+        &self,
+        domain: &str,
+        deadline: Deadline,
+    ) -> Result<CachedCapabilities, PaymailError> {
+        if let Some(entry) = self.cache.get(domain).await {
+            if entry.is_fresh() {
+                return Ok(entry);
+            }
+            if self.within_staleness(&entry) {
+                self.spawn_refresh(domain);
+                return Ok(entry);
             }
         }
-        let base_url = self.get_base_url(domain).await?;
-        let url = format!("{base_url}/.well-known/bsvalias");
-        let resp: Capabilities = self.http.get(&url).send().await?.json().await?;
-        cache.insert(
-            domain.to_string(),
-            (resp.clone(), Instant::now() + self.cache_ttl),
-        );
-        Ok(resp)
+        if let Some(err) = self.cache.get_negative(domain) {
+            return Err(err);
+        }
+        let tx = loop {
+            match self.cache.join_flight(domain) {
+                Flight::Leader(tx) => break tx,
+                Flight::Follower(mut rx) => {
+                    let shared = deadline
+                        .run(TimeoutPhase::Capabilities, async move {
+                            let result = rx.wait_for(Option::is_some).await;
+                            Ok(result.ok().and_then(|r| r.as_ref().map(share_result)))
+                        })
+                        .await?;
+                    if let Some(result) = shared {
+                        return result;
+                    }
+                }
+            }
+        };
+        // The previous flight may have finished between the checks above and
+        // joining.
+        let result = match self.cache.get_fresh(domain).await {
+            Some(entry) => Ok(entry),
+            None => match self.cache.get_negative(domain) {
+                Some(err) => Err(err),
+                None => {
+                    let result = self.fetch_capabilities(domain, deadline).await;
+                    if let Err(err) = &result {
+                        self.cache.record_failure(domain, err);
+                    }
+                    result
+                }
+            },
+        };
+        self.cache.finish_flight(domain, tx, &result);
+        result
     }
 
-    pub async fn get_pubkey(&self, paymail: &str) -> Result<String, PaymailError> {
-        let (alias, domain) = parse_paymail(paymail)?;
-        let caps = self.get_capabilities(&domain).await?;
-        let pki_endpoint = get_template(&caps, "pki", &alias, &domain)?;
-        let base_url = self.get_base_url(&domain).await?;
-        let pki_url = if pki_endpoint.starts_with('/') {
-            format!("{base_url}{pki_endpoint}")
-        } else {
-            pki_endpoint
+    fn within_staleness(&self, entry: &CachedCapabilities) -> bool {
+        self.max_staleness
+            .is_some_and(|max| Utc::now() < utc_after(entry.expires_at, max))
+    }
+
+    fn spawn_refresh(&self, domain: &str) {
+        if !self.cache.refresh_due(domain) {
+            return;
+        }
+        // A refresh (or foreground fetch) for this domain is already running.
+        let Flight::Leader(tx) = self.cache.join_flight(domain) else {
+            return;
+        };
+        let client = self.clone();
+        let domain = domain.to_string();
+        tokio::spawn(async move {
+            let result = client
+                .fetch_capabilities(&domain, client.start_deadline())
+                .await;
+            if result.is_err() {
+                client.cache.record_refresh_failure(&domain);
+            }
+            client.cache.finish_flight(&domain, tx, &result);
+        });
+    }
+
+    /// Drops both positive and negative cache entries for `domain`.
+    pub async fn purge_domain(&self, domain: &str) -> Result<(), PaymailError> {
+        self.cache.purge(&address::normalize_domain(domain)?).await
+    }
+
+    async fn fetch_capabilities(
+        &self,
+        domain: &str,
+        deadline: Deadline,
+    ) -> Result<CachedCapabilities, PaymailError> {
+        let base_urls = deadline
+            .run(TimeoutPhase::Dns, self.base_urls(domain))
+            .await?;
+        let previous = self.cache.get(domain).await;
+        let mut last_err = None;
+        // Walk the resolver's candidates, moving on only when a host could
+        // not be reached at all.
+        for base_url in base_urls {
+            match self
+                .fetch_capabilities_from(domain, base_url, previous.clone(), deadline)
+                .await
+            {
+                Err(err) if is_unreachable(&err) => last_err = Some(err),
+                result => return result,
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| PaymailError::DnsFailure(format!("No host found for {domain}"))))
+    }
+
+    async fn fetch_capabilities_from(
+        &self,
+        domain: &str,
+        base_url: String,
+        previous: Option<CachedCapabilities>,
+        deadline: Deadline,
+    ) -> Result<CachedCapabilities, PaymailError> {
+        let url = self.endpoint_url(&base_url, "/.well-known/bsvalias".to_string())?;
+        let mut request = HttpRequest::get(&url);
+        if let Some(prev) = &previous {
+            if let Some(etag) = &prev.etag {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &prev.last_modified {
+                request = request.header("If-Modified-Since", last_modified);
+            }
+        }
+        let resp = deadline
+            .run(
+                TimeoutPhase::Capabilities,
+                self.execute(request, "capabilities", true, deadline),
+            )
+            .await?;
+        let ttl = self.cache_policy.ttl_for(&resp);
+        let entry = match previous {
+            // A 304 may carry updated validators.
+            Some(prev) if resp.status == 304 => CachedCapabilities {
+                base_url,
+                etag: resp.header("etag").map(str::to_string).or(prev.etag),
+                last_modified: resp
+                    .header("last-modified")
+                    .map(str::to_string)
+                    .or(prev.last_modified),
+                ..prev
+            },
+            _ => CachedCapabilities {
+                capabilities: self
+                    .field_policy
+                    .from_slice(&resp.body)
+                    .map_err(|e| PaymailError::InvalidCapabilities(e.to_string()))?,
+                base_url,
+                fetched_at: Utc::now(),
+                expires_at: Utc::now(),
+                etag: resp.header("etag").map(str::to_string),
+                last_modified: resp.header("last-modified").map(str::to_string),
+            },
+        };
+        self.cache.put(domain, entry.clone(), ttl).await;
+        Ok(entry)
+    }
+
+    async fn entry_base_url(
+        &self,
+        domain: &str,
+        entry: &CachedCapabilities,
+        deadline: Deadline,
+    ) -> Result<String, PaymailError> {
+        if !entry.base_url.is_empty() {
+            return Ok(entry.base_url.clone());
+        }
+        deadline
+            .run(TimeoutPhase::Dns, self.get_base_url(domain))
+            .await
+    }
+
+    /// Calls `endpoint` on the host that served the capabilities. Relative
+    /// templates fail over to the domain's other candidates, in order, when
+    /// that host cannot be reached.
+    async fn call_endpoint<T, F, Fut>(
+        &self,
+        domain: &str,
+        entry: &CachedCapabilities,
+        endpoint: String,
+        deadline: Deadline,
+        call: F,
+    ) -> Result<T, PaymailError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, PaymailError>>,
+    {
+        let base_url = self.entry_base_url(domain, entry, deadline).await?;
+        let url = self.endpoint_url(&base_url, endpoint.clone())?;
+        let mut last_err = match deadline.run(TimeoutPhase::Endpoint, call(url)).await {
+            Err(err) if endpoint.starts_with('/') && is_unreachable(&err) => err,
+            result => return result,
         };
-        let resp: PkiResponse = self.http.get(&pki_url).send().await?.json().await?;
+        let base_urls = deadline
+            .run(TimeoutPhase::Dns, self.base_urls(domain))
+            .await?;
+        for other in base_urls.into_iter().filter(|other| *other != base_url) {
+            let url = self.endpoint_url(&other, endpoint.clone())?;
+            match deadline.run(TimeoutPhase::Endpoint, call(url)).await {
+                Err(err) if is_unreachable(&err) => last_err = err,
+                result => return result,
+            }
+        }
+        Err(last_err)
+    }
+
+    pub async fn get_pubkey(
+        &self,
+        paymail: impl IntoPaymailAddress,
+    ) -> Result<String, PaymailError> {
+        let address = paymail.into_paymail_address()?;
+        let (alias, domain) = (address.alias(), address.domain());
+        let deadline = self.start_deadline();
+        let entry = self.capabilities_within(domain, deadline).await?;
+        let pki_endpoint = get_template(&entry.capabilities, Brfc::Pki.name(), alias, domain)?;
+        let resp: PkiResponse = self
+            .call_endpoint(domain, &entry, pki_endpoint, deadline, |url| async move {
+                self.get_json(&url, Brfc::Pki.name(), deadline).await
+            })
+            .await?;
         Ok(resp.pubkey)
     }
 
     pub async fn get_payment_destination(
         &self,
-        paymail: &str,
+        paymail: impl IntoPaymailAddress,
         mut req: PaymentRequest,
     ) -> Result<String, PaymailError> {
-        let (alias, domain) = parse_paymail(paymail)?;
-        let caps = self.get_capabilities(&domain).await?;
-        let endpoint = get_template(&caps, "paymentDestination", &alias, &domain)?;
-        let base_url = self.get_base_url(&domain).await?;
-        let full_endpoint = if endpoint.starts_with('/') {
-            format!("{base_url}{endpoint}")
-        } else {
-            endpoint
-        };
+        let address = paymail.into_paymail_address()?;
+        let (alias, domain) = (address.alias(), address.domain());
+        let deadline = self.start_deadline();
+        let entry = self.capabilities_within(domain, deadline).await?;
+        let endpoint = get_template(
+            &entry.capabilities,
+            Brfc::PaymentDestination.name(),
+            alias,
+            domain,
+        )?;
         req.dt = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
-        req.signature = utils::generate_signature(&self.priv_key, &req.signable_message())?;
+        req.signature = utils::generate_signature_with(
+            &self.priv_key,
+            &req.signable_message(),
+            self.signature_scheme,
+        )?;
+        let req = &req;
         let resp: PaymentDestinationResponse = self
-            .http
-            .post(&full_endpoint)
-            .json(&req)
-            .send()
-            .await?
-            .json()
+            .call_endpoint(domain, &entry, endpoint, deadline, |url| async move {
+                self.post_json(&url, req, Brfc::PaymentDestination.name(), true, deadline)
+                    .await
+            })
             .await?;
         Ok(resp.output)
     }
 
     pub async fn get_p2p_payment_destination(
         &self,
-        paymail: &str,
+        paymail: impl IntoPaymailAddress,
         satoshis: u64,
     ) -> Result<P2PPaymentDestinationResponse, PaymailError> {
-        let (alias, domain) = parse_paymail(paymail)?;
-        let caps = self.get_capabilities(&domain).await?;
-        let endpoint = get_template(&caps, "2a40af698840", &alias, &domain)?;
-        let base_url = self.get_base_url(&domain).await?;
-        let full_endpoint = if endpoint.starts_with('/') {
-            format!("{base_url}{endpoint}")
-        } else {
-            endpoint
-        };
-        let req = P2PPaymentDestinationRequest { satoshis };
+        let address = paymail.into_paymail_address()?;
+        let (alias, domain) = (address.alias(), address.domain());
+        let deadline = self.start_deadline();
+        let entry = self.capabilities_within(domain, deadline).await?;
+        let endpoint = get_template(
+            &entry.capabilities,
+            Brfc::P2pPaymentDestination.name(),
+            alias,
+            domain,
+        )?;
+        let req = &P2PPaymentDestinationRequest { satoshis };
         let resp: P2PPaymentDestinationResponse = self
-            .http
-            .post(&full_endpoint)
-            .json(&req)
-            .send()
-            .await?
-            .json()
+            .call_endpoint(domain, &entry, endpoint, deadline, |url| async move {
+                self.post_json(
+                    &url,
+                    req,
+                    Brfc::P2pPaymentDestination.name(),
+                    true,
+                    deadline,
+                )
+                .await
+            })
             .await?;
         Ok(resp)
     }
 
     pub async fn send_p2p_tx(
         &self,
-        paymail: &str,
+        paymail: impl IntoPaymailAddress,
         hex: &str,
-        metadata: Value,
+        metadata: P2PTxMetadata,
         reference: &str,
     ) -> Result<P2PTxResponse, PaymailError> {
-        let (alias, domain) = parse_paymail(paymail)?;
-        let caps = self.get_capabilities(&domain).await?;
-        let endpoint = get_template(&caps, "5f1323cddf31", &alias, &domain)?;
-        let base_url = self.get_base_url(&domain).await?;
-        let full_endpoint = if endpoint.starts_with('/') {
-            format!("{base_url}{endpoint}")
-        } else {
-            endpoint
-        };
-        let message = format!("{hex}|{reference}");
-        let signature = utils::generate_signature(&self.priv_key, &message)?;
-        let req = P2PTxRequest {
+        let address = paymail.into_paymail_address()?;
+        let (alias, domain) = (address.alias(), address.domain());
+        let deadline = self.start_deadline();
+        let entry = self.capabilities_within(domain, deadline).await?;
+        let endpoint = get_template(
+            &entry.capabilities,
+            Brfc::P2pReceiveTransaction.name(),
+            alias,
+            domain,
+        )?;
+        let txid = utils::txid(hex)?;
+        let signature =
+            utils::generate_signature_with(&self.priv_key, &txid, self.signature_scheme)?;
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &self.priv_key);
+        let req = &P2PTxRequest {
             hex: hex.to_string(),
-            metadata,
+            metadata: P2PTxMetadata {
+                pubkey: Some(pubkey.to_string()),
+                signature: Some(signature),
+                ..metadata
+            },
             reference: reference.to_string(),
-            signature,
         };
         let resp: P2PTxResponse = self
-            .http
-            .post(&full_endpoint)
-            .json(&req)
-            .send()
-            .await?
-            .json()
+            .call_endpoint(domain, &entry, endpoint, deadline, |url| async move {
+                self.post_json(
+                    &url,
+                    req,
+                    Brfc::P2pReceiveTransaction.name(),
+                    false,
+                    deadline,
+                )
+                .await
+            })
             .await?;
         Ok(resp)
     }
 
     pub async fn call_extension(
         &self,
-        paymail: &str,
+        paymail: impl IntoPaymailAddress,
         brfc_id: &str,
         body: Option<Value>,
     ) -> Result<Value, PaymailError> {
-        let (alias, domain) = parse_paymail(paymail)?;
-        let caps = self.get_capabilities(&domain).await?;
-        let endpoint = get_template(&caps, brfc_id, &alias, &domain)?;
-        let base_url = self.get_base_url(&domain).await?;
-        let full_endpoint = if endpoint.starts_with('/') {
-            format!("{base_url}{endpoint}")
-        } else {
-            endpoint
-        };
-        let resp = if let Some(b) = body {
-            self.http.post(&full_endpoint).json(&b).send().await?
-        } else {
-            self.http.get(&full_endpoint).send().await?
-        };
-        let json: Value = resp.json().await?;
-        Ok(json)
+        let address = paymail.into_paymail_address()?;
+        let (alias, domain) = (address.alias(), address.domain());
+        let deadline = self.start_deadline();
+        let entry = self.capabilities_within(domain, deadline).await?;
+        let endpoint = get_template(&entry.capabilities, brfc_id, alias, domain)?;
+        let body = &body;
+        self.call_endpoint(domain, &entry, endpoint, deadline, |url| async move {
+            match body {
+                Some(b) => self.post_json(&url, b, brfc_id, false, deadline).await,
+                None => self.get_json(&url, brfc_id, deadline).await,
+            }
+        })
+        .await
+    }
+
+    async fn get_json<T: DeserializeOwned + Serialize>(
+        &self,
+        url: &str,
+        capability: &str,
+        deadline: Deadline,
+    ) -> Result<T, PaymailError> {
+        let resp = self
+            .execute(HttpRequest::get(url), capability, true, deadline)
+            .await?;
+        Ok(self.field_policy.from_slice(&resp.body)?)
+    }
+
+    async fn post_json<T: DeserializeOwned + Serialize, B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+        capability: &str,
+        idempotent: bool,
+        deadline: Deadline,
+    ) -> Result<T, PaymailError> {
+        let request = HttpRequest::post_json(url, serde_json::to_vec(body)?);
+        let resp = self
+            .execute(request, capability, idempotent, deadline)
+            .await?;
+        Ok(self.field_policy.from_slice(&resp.body)?)
+    }
+
+    async fn execute(
+        &self,
+        mut request: HttpRequest,
+        capability: &str,
+        idempotent: bool,
+        deadline: Deadline,
+    ) -> Result<HttpResponse, PaymailError> {
+        request.timeout = request.timeout.or(self.request_timeout);
+        let request = &request;
+        self.with_retry(idempotent, deadline, || async move {
+            let resp = self.http.send(request.clone()).await?;
+            // Caller-supplied transports may follow redirects on their own.
+            self.scheme_policy.check_url(&resp.url)?;
+            check_status(resp, capability)
+        })
+        .await
+    }
+
+    /// Gives up early, returning the last error, when the next attempt could
+    /// not start before `deadline` or the server asks for a longer wait than
+    /// the policy allows.
+    async fn with_retry<T, F, Fut>(
+        &self,
+        idempotent: bool,
+        deadline: Deadline,
+        mut op: F,
+    ) -> Result<T, PaymailError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, PaymailError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(err)
+                    if attempt < self.retry_policy.max_attempts
+                        && self.retry_policy.should_retry(&err, idempotent) =>
+                {
+                    let delay = self.retry_policy.delay_for(&err, attempt);
+                    let Some(delay) = delay.filter(|delay| deadline.allows(*delay)) else {
+                        return Err(err);
+                    };
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
     }
 }
 
 pub struct PaymailClientBuilder {
-    cache_ttl: Duration,
+    cache_policy: CachePolicy,
+    negative_cache_ttl: Duration,
     resolver: Option<Arc<dyn Resolver + Send + Sync>>,
+    transport: Option<Arc<dyn HttpTransport + Send + Sync>>,
+    capability_store: Option<Arc<dyn CapabilityStore + Send + Sync>>,
+    scheme_policy: SchemePolicy,
+    retry_policy: RetryPolicy,
+    signature_scheme: SignatureScheme,
+    field_policy: UnknownFieldPolicy,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    deadline: Option<Duration>,
+    max_staleness: Option<Duration>,
 }
 
 impl Default for PaymailClientBuilder {
     fn default() -> Self {
         Self {
-            cache_ttl: Duration::from_secs(3600),
+            cache_policy: CachePolicy::default(),
+            negative_cache_ttl: Duration::from_secs(300),
             resolver: None,
+            transport: None,
+            capability_store: None,
+            scheme_policy: SchemePolicy::https_only(),
+            retry_policy: RetryPolicy::default(),
+            signature_scheme: SignatureScheme::default(),
+            field_policy: UnknownFieldPolicy::default(),
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            deadline: None,
+            max_staleness: None,
         }
     }
 }
 
 impl PaymailClientBuilder {
+    /// Lifetime used when the provider sends no caching headers.
     pub fn cache_ttl(mut self, ttl: Duration) -> Self {
-        self.cache_ttl = ttl;
+        self.cache_policy.default_ttl = ttl;
+        self
+    }
+
+    pub fn min_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_policy.min_ttl = ttl;
+        self
+    }
+
+    pub fn max_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_policy.max_ttl = ttl;
         self
     }
 
@@ -205,25 +870,196 @@ impl PaymailClientBuilder {
         self
     }
 
+    /// How long failed lookups (no host, 404 or invalid capability document)
+    /// are remembered; `Duration::ZERO` disables negative caching.
+    pub fn negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_cache_ttl = ttl;
+        self
+    }
+
+    pub fn capability_store(mut self, store: Arc<dyn CapabilityStore + Send + Sync>) -> Self {
+        self.capability_store = Some(store);
+        self
+    }
+
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport + Send + Sync>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Reuses a preconfigured `reqwest::Client`; `connect_timeout` then has
+    /// to be set on that client directly. So does the redirect policy: build
+    /// it with `redirect::Policy::none()` or a policy that enforces the same
+    /// scheme rules. Responses that end on a URL the scheme policy rejects
+    /// fail with `InsecureScheme`, but only after the request was sent.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.transport = Some(Arc::new(ReqwestTransport::new(client)));
+        self
+    }
+
+    pub fn scheme_policy(mut self, policy: SchemePolicy) -> Self {
+        self.scheme_policy = policy;
+        self
+    }
+
+    pub fn allow_plaintext_host(mut self, host: &str) -> Self {
+        self.scheme_policy = self.scheme_policy.allow_plaintext(host);
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Defaults to Bitcoin Signed Message; `SignatureScheme::Legacy` is only
+    /// for counterparties that still expect this crate's old signatures.
+    pub fn signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.signature_scheme = scheme;
+        self
+    }
+
+    /// Lenient by default; `Strict` fails responses carrying fields the
+    /// models do not know, which helps catch spec drift in providers.
+    pub fn unknown_fields(mut self, policy: UnknownFieldPolicy) -> Self {
+        self.field_policy = policy;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Overall budget for one call, covering DNS resolution, capability
+    /// discovery and the endpoint request (including retries).
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Serves expired capabilities for up to `max_staleness` past their expiry
+    /// while a background task refreshes them.
+    pub fn stale_while_revalidate(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = Some(max_staleness);
+        self
+    }
+
     pub fn build(self, priv_key: SecretKey) -> PaymailClient {
+        let connect_timeout = self.connect_timeout;
+        let scheme_policy = self.scheme_policy.clone();
+        let http = self.transport.unwrap_or_else(|| {
+            let mut client = Client::builder().redirect(redirect_policy(scheme_policy));
+            if let Some(timeout) = connect_timeout {
+                client = client.connect_timeout(timeout);
+            }
+            let client = client.build().unwrap_or_else(|_| Client::new());
+            Arc::new(ReqwestTransport::new(client))
+        });
         PaymailClient {
-            http: Arc::new(Client::new()),
-            cache: Arc::new(Mutex::new(HashMap::new())),
-            cache_ttl: self.cache_ttl,
+            http,
+            cache: Arc::new(CapabilityCache::new(
+                self.capability_store
+                    .unwrap_or_else(|| Arc::new(MemoryCapabilityStore::default())),
+                self.negative_cache_ttl,
+            )),
+            cache_policy: self.cache_policy,
             priv_key,
+            signature_scheme: self.signature_scheme,
+            field_policy: self.field_policy,
             resolver: self
                 .resolver
-                .unwrap_or_else(|| Arc::new(crate::resolver::DefaultResolver)),
+                .unwrap_or_else(|| Arc::new(crate::resolver::DefaultResolver::default())),
+            scheme_policy: self.scheme_policy,
+            retry_policy: self.retry_policy,
+            request_timeout: self.request_timeout,
+            deadline: self.deadline,
+            max_staleness: self.max_staleness,
+        }
+    }
+}
+
+/// `host` lowercased and without IPv6 brackets, as URLs report it with them.
+fn plain_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase()
+}
+
+/// Follows redirects only to URLs the scheme policy accepts, so a provider
+/// cannot bounce a request onto plaintext http.
+fn redirect_policy(scheme_policy: SchemePolicy) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        match scheme_policy.check_url(attempt.url().as_str()) {
+            Ok(()) => attempt.follow(),
+            Err(err) => attempt.error(err),
         }
+    })
+}
+
+/// The host could not be reached at all, so another candidate may be tried
+/// even for non-idempotent calls.
+fn is_unreachable(err: &PaymailError) -> bool {
+    matches!(
+        err,
+        PaymailError::Connection(_) | PaymailError::Timeout(TimeoutPhase::Connect)
+    )
+}
+
+fn share_result(result: &FlightResult) -> FlightResult {
+    match result {
+        Ok(entry) => Ok(entry.clone()),
+        Err(err) => Err(err.duplicate()),
+    }
+}
+
+/// `None` when `ttl` reaches past what `Instant` can represent, which the
+/// in-memory caches treat as never expiring.
+fn instant_after(ttl: Duration) -> Expiry {
+    Instant::now().checked_add(ttl)
+}
+
+fn is_expired(expiry: Expiry) -> bool {
+    expiry.is_some_and(|at| Instant::now() >= at)
+}
+
+/// `from + ttl`, saturating at the latest representable time so that
+/// "cache forever" TTLs cannot overflow.
+fn utc_after(from: DateTime<Utc>, ttl: Duration) -> DateTime<Utc> {
+    TimeDelta::from_std(ttl)
+        .ok()
+        .and_then(|delta| from.checked_add_signed(delta))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn check_status(resp: HttpResponse, capability: &str) -> Result<HttpResponse, PaymailError> {
+    if resp.is_success() || resp.status == 304 {
+        return Ok(resp);
     }
+    let retry_after = resp.header("retry-after").and_then(parse_retry_after);
+    Err(PaymailError::from_http_status(
+        resp.status,
+        &resp.url,
+        capability,
+        &String::from_utf8_lossy(&resp.body),
+        retry_after,
+    ))
 }
 
-fn parse_paymail(paymail: &str) -> Result<(String, String), PaymailError> {
-    let parts: Vec<&str> = paymail.split('@').collect();
-    if parts.len() != 2 {
-        return Err(PaymailError::InvalidFormat(paymail.to_string()));
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
+    let at = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    (at.with_timezone(&Utc) - Utc::now()).to_std().ok()
 }
 
 fn get_template(
@@ -232,7 +1068,12 @@ fn get_template(
     alias: &str,
     domain: &str,
 ) -> Result<String, PaymailError> {
-    if let Some(Value::String(tmpl)) = caps.capabilities.get(key) {
+    // Known capabilities match under either their alias or BRFC ID.
+    let entry = match Brfc::from_key(key) {
+        Some(brfc) => caps.get(brfc),
+        None => caps.capabilities.get(key),
+    };
+    if let Some(Value::String(tmpl)) = entry {
         Ok(tmpl
             .replace("{alias}", alias)
             .replace("{domain.tld}", domain))
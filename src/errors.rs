@@ -1,16 +1,43 @@
+use std::fmt;
+use std::time::Duration;
+
 use sv::util::Error as SvError;
 use thiserror::Error;
 
+use crate::models::ErrorResponse;
+
+const MAX_ERROR_BODY_LEN: usize = 512;
+
 #[derive(Error, Debug)]
 pub enum PaymailError {
     #[error("Invalid PayMail format: {0}")]
     InvalidFormat(String),
     #[error("DNS resolution failed: {0}")]
     DnsFailure(String),
+    #[error("DNS lookup did not complete: {0}")]
+    DnsUnavailable(String),
+    #[error("Insecure URL scheme rejected: {0}")]
+    InsecureScheme(String),
+    #[error("Connection failed: {0}")]
+    Connection(String),
     #[error("HTTP request failed: {0}")]
     HttpError(#[from] reqwest::Error),
+    #[error("Not found: {0}")]
+    NotFound(Box<HttpErrorDetails>),
+    #[error("Rate limited: {0}")]
+    RateLimited(Box<HttpErrorDetails>),
+    #[error("Provider error: {0}")]
+    ProviderError(Box<HttpErrorDetails>),
+    #[error("Unexpected HTTP status: {0}")]
+    HttpStatus(Box<HttpErrorDetails>),
+    #[error("Timed out during {0}")]
+    Timeout(TimeoutPhase),
     #[error("JSON serialization/deserialization failed: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid capability document: {0}")]
+    InvalidCapabilities(String),
     #[error("Capability missing: {0}")]
     CapabilityMissing(String),
     #[error("Invalid signature: {0}")]
@@ -20,3 +47,121 @@ pub enum PaymailError {
     #[error("Other error: {0}")]
     Other(String),
 }
+
+impl PaymailError {
+    pub fn from_http_status(
+        status: u16,
+        endpoint: &str,
+        capability: &str,
+        body: &str,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let details = Box::new(HttpErrorDetails {
+            status,
+            endpoint: endpoint.to_string(),
+            capability: capability.to_string(),
+            body: truncate_body(body),
+            remote: serde_json::from_str(body).ok(),
+            retry_after,
+        });
+        match status {
+            404 => PaymailError::NotFound(details),
+            429 => PaymailError::RateLimited(details),
+            500..=599 => PaymailError::ProviderError(details),
+            _ => PaymailError::HttpStatus(details),
+        }
+    }
+
+    /// Copy of this error for another caller awaiting the same operation.
+    /// Wrapped library errors that cannot be cloned keep only their message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            PaymailError::InvalidFormat(msg) => PaymailError::InvalidFormat(msg.clone()),
+            PaymailError::DnsFailure(msg) => PaymailError::DnsFailure(msg.clone()),
+            PaymailError::DnsUnavailable(msg) => PaymailError::DnsUnavailable(msg.clone()),
+            PaymailError::InsecureScheme(msg) => PaymailError::InsecureScheme(msg.clone()),
+            PaymailError::Connection(msg) => PaymailError::Connection(msg.clone()),
+            PaymailError::NotFound(d) => PaymailError::NotFound(d.clone()),
+            PaymailError::RateLimited(d) => PaymailError::RateLimited(d.clone()),
+            PaymailError::ProviderError(d) => PaymailError::ProviderError(d.clone()),
+            PaymailError::HttpStatus(d) => PaymailError::HttpStatus(d.clone()),
+            PaymailError::Timeout(phase) => PaymailError::Timeout(*phase),
+            PaymailError::Io(e) => PaymailError::Io(std::io::Error::new(e.kind(), e.to_string())),
+            PaymailError::InvalidCapabilities(msg) => {
+                PaymailError::InvalidCapabilities(msg.clone())
+            }
+            PaymailError::CapabilityMissing(msg) => PaymailError::CapabilityMissing(msg.clone()),
+            PaymailError::InvalidSignature(msg) => PaymailError::InvalidSignature(msg.clone()),
+            PaymailError::Other(msg) => PaymailError::Other(msg.clone()),
+            PaymailError::HttpError(_) | PaymailError::JsonError(_) | PaymailError::SvError(_) => {
+                PaymailError::Other(self.to_string())
+            }
+        }
+    }
+
+    pub fn http_details(&self) -> Option<&HttpErrorDetails> {
+        match self {
+            PaymailError::NotFound(d)
+            | PaymailError::RateLimited(d)
+            | PaymailError::ProviderError(d)
+            | PaymailError::HttpStatus(d) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    Dns,
+    Connect,
+    Capabilities,
+    Endpoint,
+}
+
+impl fmt::Display for TimeoutPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            TimeoutPhase::Dns => "DNS resolution",
+            TimeoutPhase::Connect => "connection",
+            TimeoutPhase::Capabilities => "capability discovery",
+            TimeoutPhase::Endpoint => "endpoint call",
+        };
+        f.write_str(phase)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpErrorDetails {
+    pub status: u16,
+    pub endpoint: String,
+    pub capability: String,
+    pub body: String,
+    pub remote: Option<ErrorResponse>,
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for HttpErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "HTTP {} from {} (capability {})",
+            self.status, self.endpoint, self.capability
+        )?;
+        match &self.remote {
+            Some(remote) => write!(f, ": {}: {}", remote.code, remote.message),
+            None if !self.body.is_empty() => write!(f, ": {}", self.body),
+            None => Ok(()),
+        }
+    }
+}
+
+fn truncate_body(body: &str) -> String {
+    if body.len() <= MAX_ERROR_BODY_LEN {
+        return body.to_string();
+    }
+    let mut end = MAX_ERROR_BODY_LEN;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &body[..end])
+}
@@ -1,12 +1,17 @@
 #![doc = "A fast, asynchronous Rust library for the BSV PayMail protocol."]
 
+pub mod address;
+pub mod cache;
 pub mod client;
 pub mod errors;
 pub mod models;
 pub mod protocols;
 pub mod resolver;
+pub mod retry;
 pub mod server;
+pub mod transport;
 pub mod utils;
 
+pub use address::PaymailAddress;
 pub use client::PaymailClient;
 pub use errors::PaymailError;
@@ -1,68 +1,226 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Whether fields a model does not know are ignored or rejected when
+/// parsing. Only top-level fields are checked; free-form members such as
+/// `metadata` accept anything either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownFieldPolicy {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+impl UnknownFieldPolicy {
+    pub fn from_slice<T: DeserializeOwned + Serialize>(
+        self,
+        bytes: &[u8],
+    ) -> Result<T, serde_json::Error> {
+        if self == UnknownFieldPolicy::Lenient {
+            return serde_json::from_slice(bytes);
+        }
+        let input: Value = serde_json::from_slice(bytes)?;
+        let parsed: T = serde_json::from_value(input.clone())?;
+        // Anything the model drops on the way in is missing when it is
+        // written back out. Nulls are skipped as they parse to `None`.
+        if let (Value::Object(input), Value::Object(known)) =
+            (&input, serde_json::to_value(&parsed)?)
+        {
+            let unknown = input
+                .iter()
+                .find(|(key, value)| !value.is_null() && !known.contains_key(*key));
+            if let Some((key, _)) = unknown {
+                return Err(serde::de::Error::custom(format!("unknown field `{key}`")));
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Well-known capabilities, keyed in capability documents by their BRFC ID
+/// or, for the original bsvalias set, a human alias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Brfc {
+    Pki,
+    PaymentDestination,
+    SenderValidation,
+    PublicProfile,
+    VerifyPublicKey,
+    P2pPaymentDestination,
+    P2pReceiveTransaction,
+    Beef,
+    Pike,
+}
+
+impl Brfc {
+    pub const ALL: [Brfc; 9] = [
+        Brfc::Pki,
+        Brfc::PaymentDestination,
+        Brfc::SenderValidation,
+        Brfc::PublicProfile,
+        Brfc::VerifyPublicKey,
+        Brfc::P2pPaymentDestination,
+        Brfc::P2pReceiveTransaction,
+        Brfc::Beef,
+        Brfc::Pike,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Brfc::Pki => "0c4339ef99c2",
+            Brfc::PaymentDestination => "759684b1a19a",
+            Brfc::SenderValidation => "6745385c3fc0",
+            Brfc::PublicProfile => "f12f968c92d6",
+            Brfc::VerifyPublicKey => "a9f510c16bde",
+            Brfc::P2pPaymentDestination => "2a40af698840",
+            Brfc::P2pReceiveTransaction => "5f1323cddf31",
+            Brfc::Beef => "5c55a7fdb7bb",
+            Brfc::Pike => "8c4ed5ef8ace",
+        }
+    }
+
+    pub fn alias(self) -> Option<&'static str> {
+        match self {
+            Brfc::Pki => Some("pki"),
+            Brfc::PaymentDestination => Some("paymentDestination"),
+            Brfc::SenderValidation => Some("senderValidation"),
+            Brfc::PublicProfile => Some("publicProfile"),
+            Brfc::VerifyPublicKey => Some("verifyPubKey"),
+            _ => None,
+        }
+    }
+
+    /// The alias where one exists, otherwise the BRFC ID.
+    pub fn name(self) -> &'static str {
+        self.alias().unwrap_or(self.id())
+    }
+
+    /// Matches either a BRFC ID or an alias.
+    pub fn from_key(key: &str) -> Option<Brfc> {
+        Brfc::ALL
+            .into_iter()
+            .find(|brfc| brfc.id() == key || brfc.alias() == Some(key))
+    }
+}
+
+/// Capability document. The raw map keeps every entry, including extensions
+/// this crate does not know, so documents round-trip unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Capabilities {
     pub bsvalias: String,
     pub capabilities: HashMap<String, Value>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+impl Capabilities {
+    /// Entry for `brfc`, published under its BRFC ID or its alias.
+    pub fn get(&self, brfc: Brfc) -> Option<&Value> {
+        self.capabilities
+            .get(brfc.id())
+            .or_else(|| brfc.alias().and_then(|alias| self.capabilities.get(alias)))
+    }
+
+    /// Endpoint template for `brfc`, if advertised as one.
+    pub fn template(&self, brfc: Brfc) -> Option<&str> {
+        self.get(brfc).and_then(Value::as_str)
+    }
+
+    /// Boolean capability such as sender validation; false when absent.
+    pub fn flag(&self, brfc: Brfc) -> bool {
+        self.get(brfc).and_then(Value::as_bool).unwrap_or(false)
+    }
+
+    /// Entries whose key is not a known BRFC ID or alias.
+    pub fn extensions(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.capabilities
+            .iter()
+            .filter(|(key, _)| Brfc::from_key(key).is_none())
+            .map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct PaymentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub sender_name: Option<String>,
     pub sender_handle: String,
     pub dt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub purpose: Option<String>,
     pub signature: String,
 }
 
 impl PaymentRequest {
+    /// `senderHandle`, `amount`, `dt` and `purpose` concatenated without
+    /// separators, as the spec and the reference clients sign them.
     pub fn signable_message(&self) -> String {
         format!(
-            "{}|{}|{}|{}",
+            "{}{}{}{}",
             self.sender_handle,
-            self.dt,
             self.amount.unwrap_or(0),
+            self.dt,
             self.purpose.as_deref().unwrap_or("")
         )
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PaymentDestinationResponse {
     pub output: String, // Hex-encoded script
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PkiResponse {
     pub bsvalias: String,
     pub handle: String,
     pub pubkey: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct P2PPaymentDestinationRequest {
     pub satoshis: u64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct P2PPaymentDestinationResponse {
     pub outputs: Vec<Value>,
     pub reference: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct P2PTxRequest {
     pub hex: String,
-    pub metadata: Value,
+    pub metadata: P2PTxMetadata,
     pub reference: String,
-    pub signature: String,
 }
 
-#[derive(Deserialize, Debug)]
+/// Sender details of a P2P transaction. `signature` signs the txid and
+/// verifies against `pubkey`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct P2PTxMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct P2PTxResponse {
     pub txid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub note: Option<String>,
 }
@@ -1,8 +1,7 @@
 use crate::{
     PaymailClient, PaymailError,
-    models::{P2PPaymentDestinationResponse, P2PTxResponse},
+    models::{P2PPaymentDestinationResponse, P2PTxMetadata, P2PTxResponse},
 };
-use serde_json::Value;
 
 pub async fn resolve_p2p_address(
     client: &PaymailClient,
@@ -16,7 +15,7 @@ pub async fn submit_p2p_tx(
     client: &PaymailClient,
     paymail: &str,
     hex: &str,
-    metadata: Value,
+    metadata: P2PTxMetadata,
     reference: &str,
 ) -> Result<P2PTxResponse, PaymailError> {
     client.send_p2p_tx(paymail, hex, metadata, reference).await
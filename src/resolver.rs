@@ -1,41 +1,411 @@
+use std::collections::HashMap;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::address;
 use crate::errors::PaymailError;
+use crate::transport::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, HttpRequest, HttpTransport, ReqwestTransport,
+};
+use crate::utils;
+use reqwest::Client;
 use trust_dns_resolver::TokioAsyncResolver;
-use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+use trust_dns_resolver::config::{
+    NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts,
+};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::lookup::SrvLookup;
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::rdata::SRV;
+use trust_dns_resolver::proto::rr::{Name, RData, RecordType};
+
+/// Where a resolved endpoint came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointSource {
+    /// `_bsvalias._tcp` SRV record.
+    Srv,
+    /// No SRV record; `{domain}:443` after an A/AAAA lookup confirmed the domain.
+    AddressFallback,
+    /// Configured override such as a `StaticResolver` entry.
+    Static,
+    /// A `HostResolver`, which only reports `host:port`.
+    Unknown,
+}
+
+/// Outcome of resolving a paymail domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedEndpoint {
+    pub host: String,
+    pub port: u16,
+    pub source: EndpointSource,
+    /// Whether the answer was DNSSEC-authenticated, or `None` when the
+    /// resolver did not check.
+    pub dnssec_validated: Option<bool>,
+    /// How long the answer may be cached, if the resolver knows.
+    pub ttl: Option<Duration>,
+    /// Every candidate in the order it should be tried; the first is
+    /// `host:port`. The client reuses this order until `ttl` runs out.
+    pub candidates: Vec<(String, u16)>,
+}
+
+impl ResolvedEndpoint {
+    pub fn new(host: &str, port: u16, source: EndpointSource) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            source,
+            dnssec_validated: None,
+            ttl: None,
+            candidates: vec![(host.to_string(), port)],
+        }
+    }
+
+    /// Returns `None` when there are no candidates.
+    pub fn from_candidates(candidates: Vec<(String, u16)>, source: EndpointSource) -> Option<Self> {
+        let (host, port) = candidates.first()?.clone();
+        Some(Self {
+            host,
+            port,
+            source,
+            dnssec_validated: None,
+            ttl: None,
+            candidates,
+        })
+    }
+
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_dnssec_validated(mut self, validated: bool) -> Self {
+        self.dnssec_validated = Some(validated);
+        self
+    }
+}
 
 #[async_trait::async_trait]
 pub trait Resolver {
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError>;
+
+    /// Just the first candidate. Named apart from `HostResolver::resolve_host`
+    /// so calls stay unambiguous with both traits in scope.
+    async fn resolve_host_port(&self, domain: &str) -> Result<(String, u16), PaymailError> {
+        let endpoint = self.resolve(domain).await?;
+        Ok((endpoint.host, endpoint.port))
+    }
+}
+
+/// The original `host:port`-only resolver interface; code that implemented
+/// `Resolver::resolve_host` implements this instead. Implementors get
+/// `Resolver` for free, reporting `EndpointSource::Unknown`.
+#[async_trait::async_trait]
+pub trait HostResolver {
     async fn resolve_host(&self, domain: &str) -> Result<(String, u16), PaymailError>;
 }
 
-pub struct DefaultResolver;
+#[async_trait::async_trait]
+impl<T: HostResolver + Sync> Resolver for T {
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError> {
+        let (host, port) = HostResolver::resolve_host(self, domain).await?;
+        Ok(ResolvedEndpoint::new(&host, port, EndpointSource::Unknown))
+    }
+}
+
+/// What to do with an SRV target outside the queried domain when the SRV
+/// answer is not DNSSEC-validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SrvTargetPolicy {
+    #[default]
+    Reject,
+    Warn,
+    Allow,
+}
+
+impl SrvTargetPolicy {
+    /// Decides on an SRV answer whose targets include `target`, which lies
+    /// outside `domain`.
+    fn check(self, domain: &str, target: &str, validated: bool) -> Result<(), PaymailError> {
+        if self == SrvTargetPolicy::Allow || validated {
+            return Ok(());
+        }
+        if self == SrvTargetPolicy::Warn {
+            log::warn!("untrusted SRV target {target} for {domain}: response not DNSSEC-validated");
+            return Ok(());
+        }
+        Err(PaymailError::DnsFailure(format!(
+            "SRV target {target} is outside {domain} and not DNSSEC-validated"
+        )))
+    }
+}
+
+/// DNS resolver backed by trust-dns. Construct it once and share it: the
+/// underlying resolver keeps its connections and record cache between calls.
+#[derive(Clone)]
+pub struct DefaultResolver {
+    resolver: TokioAsyncResolver,
+    // Separate instance for the DNSSEC check so unvalidated answers cached by
+    // `resolver` are never mistaken for validated ones.
+    validating: TokioAsyncResolver,
+    srv_target_policy: SrvTargetPolicy,
+}
+
+impl DefaultResolver {
+    pub fn new(config: ResolverConfig, opts: ResolverOpts) -> Self {
+        let mut validating_opts = opts;
+        validating_opts.validate = true;
+        Self {
+            validating: TokioAsyncResolver::tokio(config.clone(), validating_opts),
+            resolver: TokioAsyncResolver::tokio(config, opts),
+            srv_target_policy: SrvTargetPolicy::default(),
+        }
+    }
+
+    /// Uses the host's resolver configuration (`/etc/resolv.conf` on Unix).
+    pub fn from_system_conf() -> Result<Self, PaymailError> {
+        let (config, opts) = trust_dns_resolver::system_conf::read_system_conf()
+            .map_err(|e| PaymailError::DnsFailure(format!("Invalid system DNS config: {e}")))?;
+        Ok(Self::new(config, opts))
+    }
+
+    /// Queries only the given nameservers, over UDP with TCP fallback.
+    pub fn from_nameservers(nameservers: &[SocketAddr]) -> Self {
+        let group: NameServerConfigGroup = nameservers
+            .iter()
+            .flat_map(|addr| {
+                [
+                    NameServerConfig::new(*addr, Protocol::Udp),
+                    NameServerConfig::new(*addr, Protocol::Tcp),
+                ]
+            })
+            .collect::<Vec<_>>()
+            .into();
+        Self::new(
+            ResolverConfig::from_parts(None, Vec::new(), group),
+            ResolverOpts::default(),
+        )
+    }
+
+    pub fn with_srv_target_policy(mut self, policy: SrvTargetPolicy) -> Self {
+        self.srv_target_policy = policy;
+        self
+    }
+
+    /// Checks foreign SRV targets against `resolver` instead of a validating
+    /// copy of the main one. Every answer it returns is trusted as
+    /// DNSSEC-validated, so it must set `validate` or sit in front of a
+    /// validating recursor you trust.
+    pub fn with_validating_resolver(mut self, resolver: TokioAsyncResolver) -> Self {
+        self.validating = resolver;
+        self
+    }
+
+    async fn validated_srv(&self, srv_query: &str) -> Option<SrvLookup> {
+        self.validating
+            .srv_lookup(srv_query)
+            .await
+            .ok()
+            .filter(|srv| srv.iter().next().is_some())
+    }
+}
 
 #[async_trait::async_trait]
 impl Resolver for DefaultResolver {
-    async fn resolve_host(&self, domain: &str) -> Result<(String, u16), PaymailError> {
-        let resolver =
-            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default());
+    /// SRV answers are only re-checked against a validating resolver when a
+    /// target lies outside the domain, so `dnssec_validated` is `None` for
+    /// in-domain answers and address fallbacks. When that check passes, the
+    /// candidates and TTL come from the validated answer, not the first one.
+    ///
+    /// Only NXDOMAIN or empty answers yield `DnsFailure`; timeouts, SERVFAIL
+    /// and unreachable nameservers yield `DnsUnavailable`.
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError> {
+        let resolver = &self.resolver;
         let srv_query = format!("_bsvalias._tcp.{}", domain);
-        #[allow(clippy::collapsible_if)]
-        if let Ok(srv) = resolver.srv_lookup(&srv_query).await {
-            if let Some(record) = srv.iter().next() {
-                let target = record
-                    .target()
-                    .to_string()
-                    .trim_end_matches('.')
-                    .to_string();
-                return Ok((target, record.port()));
+        let srv = match resolver.srv_lookup(&srv_query).await {
+            Ok(srv) => Some(srv),
+            Err(err) if is_no_records(&err) => None,
+            Err(err) => return Err(unavailable(&srv_query, err)),
+        };
+        if let Some(mut srv) = srv {
+            let mut targets = order_srv_records(srv.iter().cloned().collect());
+            let mut validated = None;
+            if let Some(target) = foreign_target(domain, &targets)
+                && self.srv_target_policy != SrvTargetPolicy::Allow
+            {
+                // Trusting the foreign target only covers what the
+                // validating resolver returned, so answer from that.
+                match self.validated_srv(&srv_query).await {
+                    Some(validated_srv) => {
+                        srv = validated_srv;
+                        targets = order_srv_records(srv.iter().cloned().collect());
+                        validated = Some(true);
+                    }
+                    None => {
+                        self.srv_target_policy.check(domain, target, false)?;
+                        validated = Some(false);
+                    }
+                }
             }
+            if let Some(endpoint) = ResolvedEndpoint::from_candidates(targets, EndpointSource::Srv)
+            {
+                return Ok(ResolvedEndpoint {
+                    dnssec_validated: validated,
+                    ..endpoint.with_ttl(ttl_until(srv.as_lookup().valid_until()))
+                });
+            }
+        }
+        // Per spec the fallback is `{domain}:443`; the A/AAAA lookups only
+        // confirm the domain exists, so TLS still validates against its name.
+        let domain = domain.trim_end_matches('.');
+        let valid_until = match resolver.ipv4_lookup(domain).await {
+            Ok(lookup) if lookup.iter().next().is_some() => Some(lookup.as_lookup().valid_until()),
+            Err(err) if !is_no_records(&err) => return Err(unavailable(domain, err)),
+            _ => match resolver.ipv6_lookup(domain).await {
+                Ok(lookup) if lookup.iter().next().is_some() => {
+                    Some(lookup.as_lookup().valid_until())
+                }
+                Err(err) if !is_no_records(&err) => return Err(unavailable(domain, err)),
+                _ => None,
+            },
+        };
+        if let Some(valid_until) = valid_until {
+            return Ok(
+                ResolvedEndpoint::new(domain, 443, EndpointSource::AddressFallback)
+                    .with_ttl(ttl_until(valid_until)),
+            );
+        }
+        Err(PaymailError::DnsFailure(format!(
+            "No host found for {}",
+            domain
+        )))
+    }
+}
+
+/// Resolves over DNS-over-HTTPS (RFC 8484), for networks where port 53 is
+/// blocked but HTTPS egress is allowed. Queries are POSTed in DNS wire
+/// format to the configured endpoint, e.g. `https://dns.example/dns-query`.
+///
+/// An answer counts as DNSSEC-validated when an `https` endpoint sets the
+/// AD flag, so only point this at a validating resolver you trust.
+#[derive(Clone)]
+pub struct DohResolver {
+    endpoint: String,
+    transport: Arc<dyn HttpTransport + Send + Sync>,
+    srv_target_policy: SrvTargetPolicy,
+}
+
+impl DohResolver {
+    /// Uses a reqwest transport with the same connect and request timeouts
+    /// as `PaymailClient::builder()`; see `with_transport` to change them.
+    pub fn new(endpoint: &str) -> Self {
+        let client = Client::builder()
+            .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+            .timeout(DEFAULT_REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Self {
+            endpoint: endpoint.to_string(),
+            transport: Arc::new(ReqwestTransport::new(client)),
+            srv_target_policy: SrvTargetPolicy::default(),
+        }
+    }
+
+    pub fn with_transport(mut self, transport: Arc<dyn HttpTransport + Send + Sync>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn with_srv_target_policy(mut self, policy: SrvTargetPolicy) -> Self {
+        self.srv_target_policy = policy;
+        self
+    }
+
+    async fn query(&self, name: &str, record_type: RecordType) -> Result<Message, PaymailError> {
+        let qname = Name::from_ascii(name)
+            .map_err(|e| PaymailError::DnsFailure(format!("Invalid name {name}: {e}")))?;
+        let mut query = Message::new();
+        // RFC 8484 §4.1: a zero ID keeps identical queries cacheable.
+        query
+            .set_id(0)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(true)
+            .set_authentic_data(true)
+            .add_query(Query::query(qname, record_type));
+        let body = query
+            .to_vec()
+            .map_err(|e| PaymailError::DnsFailure(format!("Failed to encode query: {e}")))?;
+        let request = HttpRequest::post(&self.endpoint, "application/dns-message", body)
+            .header("accept", "application/dns-message");
+        // An unreachable endpoint is an outage, not an answer, so chains
+        // move on to their next resolver.
+        let response = self.transport.send(request).await.map_err(|e| {
+            PaymailError::DnsUnavailable(format!("DoH request to {} failed: {e}", self.endpoint))
+        })?;
+        if !response.is_success() {
+            return Err(PaymailError::DnsUnavailable(format!(
+                "DoH endpoint returned status {}",
+                response.status
+            )));
+        }
+        let message = Message::from_vec(&response.body)
+            .map_err(|e| PaymailError::DnsUnavailable(format!("Invalid DoH response: {e}")))?;
+        match message.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => Ok(message),
+            code => Err(PaymailError::DnsUnavailable(format!(
+                "DoH lookup for {name} failed: {code}"
+            ))),
         }
-        #[allow(clippy::collapsible_if)]
-        if let Ok(a_lookup) = resolver.ipv4_lookup(domain).await {
-            if let Some(ip) = a_lookup.iter().next() {
-                return Ok((ip.to_string(), 443));
+    }
+
+    /// Records of `record_type` in the answer, with the lowest TTL among them.
+    fn answers(message: &Message, record_type: RecordType) -> (Vec<RData>, Option<Duration>) {
+        let records: Vec<_> = message
+            .answers()
+            .iter()
+            .filter(|r| r.record_type() == record_type)
+            .collect();
+        let ttl = records.iter().map(|r| r.ttl()).min();
+        let data = records.iter().filter_map(|r| r.data().cloned()).collect();
+        (data, ttl.map(|ttl| Duration::from_secs(u64::from(ttl))))
+    }
+}
+
+#[async_trait::async_trait]
+impl Resolver for DohResolver {
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError> {
+        let secure = self.endpoint.starts_with("https://");
+        let srv_query = format!("_bsvalias._tcp.{}", domain);
+        let message = self.query(&srv_query, RecordType::SRV).await?;
+        let (records, ttl) = Self::answers(&message, RecordType::SRV);
+        let srv = records
+            .into_iter()
+            .filter_map(|data| match data {
+                RData::SRV(srv) => Some(srv),
+                _ => None,
+            })
+            .collect();
+        let targets = order_srv_records(srv);
+        if let Some(endpoint) = ResolvedEndpoint::from_candidates(targets, EndpointSource::Srv) {
+            let validated = secure && message.authentic_data();
+            if let Some(target) = foreign_target(domain, &endpoint.candidates) {
+                self.srv_target_policy.check(domain, target, validated)?;
             }
+            return Ok(endpoint.with_ttl(ttl).with_dnssec_validated(validated));
         }
-        #[allow(clippy::collapsible_if)]
-        if let Ok(aaaa_lookup) = resolver.ipv6_lookup(domain).await {
-            if let Some(ip) = aaaa_lookup.iter().next() {
-                return Ok((ip.to_string(), 443));
+        let domain = domain.trim_end_matches('.');
+        for record_type in [RecordType::A, RecordType::AAAA] {
+            let message = self.query(domain, record_type).await?;
+            let (records, ttl) = Self::answers(&message, record_type);
+            if !records.is_empty() {
+                return Ok(
+                    ResolvedEndpoint::new(domain, 443, EndpointSource::AddressFallback)
+                        .with_ttl(ttl)
+                        .with_dnssec_validated(secure && message.authentic_data()),
+                );
             }
         }
         Err(PaymailError::DnsFailure(format!(
@@ -45,6 +415,217 @@ impl Resolver for DefaultResolver {
     }
 }
 
+/// Fixed domain → `host:port` map, for tests and private networks.
+///
+/// Adding several entries for one domain yields them as ordered candidates.
+/// Unknown domains fail with `DnsFailure`, so a `ChainResolver` moves on.
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<(String, u16)>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `domain` is normalised like a paymail address's domain, so an IDN
+    /// entry matches its punycode form; invalid domains are rejected.
+    pub fn with_host(mut self, domain: &str, host: &str, port: u16) -> Result<Self, PaymailError> {
+        self.hosts
+            .entry(address::normalize_domain(domain)?)
+            .or_default()
+            .push((host.to_string(), port));
+        Ok(self)
+    }
+
+    /// Loads a hosts-like file; see the `FromStr` impl for the format.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PaymailError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+impl FromStr for StaticResolver {
+    type Err = PaymailError;
+
+    /// One `domain host[:port]` entry per line; the port defaults to 443.
+    /// IPv6 hosts are written bare (`::1`) or, with a port, in brackets
+    /// (`[::1]:8443`). Blank lines and `#` comments are ignored.
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let mut resolver = Self::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                PaymailError::InvalidFormat(format!("Invalid host entry on line {}", number + 1))
+            };
+            let mut fields = line.split_whitespace();
+            let (Some(domain), Some(target), None) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let (host, port) = if let Some(rest) = target.strip_prefix('[') {
+                let (host, port) = rest.split_once(']').ok_or_else(invalid)?;
+                match port.strip_prefix(':') {
+                    Some(port) => (host, port.parse().map_err(|_| invalid())?),
+                    None if port.is_empty() => (host, 443),
+                    None => return Err(invalid()),
+                }
+            } else if target.parse::<Ipv6Addr>().is_ok() {
+                (target, 443)
+            } else {
+                match target.rsplit_once(':') {
+                    Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                    None => (target, 443),
+                }
+            };
+            if host.is_empty() {
+                return Err(invalid());
+            }
+            resolver = resolver
+                .with_host(domain, host, port)
+                .map_err(|_| invalid())?;
+        }
+        Ok(resolver)
+    }
+}
+
+#[async_trait::async_trait]
+impl Resolver for StaticResolver {
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError> {
+        address::normalize_domain(domain)
+            .ok()
+            .and_then(|domain| self.hosts.get(&domain))
+            .and_then(|hosts| {
+                ResolvedEndpoint::from_candidates(hosts.clone(), EndpointSource::Static)
+            })
+            .ok_or_else(|| PaymailError::DnsFailure(format!("No static host for {}", domain)))
+    }
+}
+
+/// Consults each resolver in turn until one knows the domain, e.g. static
+/// overrides in front of a `DefaultResolver`. Only `DnsFailure` and
+/// `DnsUnavailable` fall through, and if every resolver failed a transient
+/// failure is reported over a missing domain. Any other error is returned
+/// as is.
+#[derive(Clone)]
+pub struct ChainResolver {
+    resolvers: Vec<Arc<dyn Resolver + Send + Sync>>,
+}
+
+impl ChainResolver {
+    pub fn new(resolvers: Vec<Arc<dyn Resolver + Send + Sync>>) -> Self {
+        Self { resolvers }
+    }
+
+    /// `overrides` first, then DNS via the default resolver.
+    pub fn with_overrides(overrides: StaticResolver) -> Self {
+        Self::new(vec![
+            Arc::new(overrides),
+            Arc::new(DefaultResolver::default()),
+        ])
+    }
+}
+
+#[async_trait::async_trait]
+impl Resolver for ChainResolver {
+    async fn resolve(&self, domain: &str) -> Result<ResolvedEndpoint, PaymailError> {
+        let (mut not_found, mut unavailable) = (None, None);
+        for resolver in &self.resolvers {
+            match resolver.resolve(domain).await {
+                Ok(endpoint) => return Ok(endpoint),
+                Err(err @ PaymailError::DnsFailure(_)) => not_found = Some(err),
+                Err(err @ PaymailError::DnsUnavailable(_)) => unavailable = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(unavailable
+            .or(not_found)
+            .unwrap_or_else(|| PaymailError::DnsFailure(format!("No host found for {}", domain))))
+    }
+}
+
+/// Orders SRV records per RFC 2782: ascending priority, and weighted random
+/// selection among records sharing a priority. A lone "." target means the
+/// service is explicitly unavailable and yields no endpoints.
+pub fn order_srv_records(mut records: Vec<SRV>) -> Vec<(String, u16)> {
+    records.retain(|r| !r.target().is_root());
+    records.sort_by_key(|r| r.priority());
+    let mut ordered = Vec::with_capacity(records.len());
+    for group in records.chunk_by(|a, b| a.priority() == b.priority()) {
+        // Zero-weight records go first so they only win when drawn at 0.
+        let mut remaining: Vec<&SRV> = group.iter().collect();
+        remaining.sort_by_key(|r| r.weight() != 0);
+        while !remaining.is_empty() {
+            let total: u64 = remaining.iter().map(|r| u64::from(r.weight())).sum();
+            let pick = utils::random_u64() % (total + 1);
+            let mut running = 0;
+            let index = remaining
+                .iter()
+                .position(|r| {
+                    running += u64::from(r.weight());
+                    running >= pick
+                })
+                .unwrap_or(0);
+            let record = remaining.remove(index);
+            let target = record.target().to_string();
+            ordered.push((target.trim_end_matches('.').to_string(), record.port()));
+        }
+    }
+    ordered
+}
+
+/// First SRV target that does not belong to `domain`, if any.
+fn foreign_target<'a>(domain: &str, targets: &'a [(String, u16)]) -> Option<&'a str> {
+    targets
+        .iter()
+        .map(|(target, _)| target.as_str())
+        .find(|target| !is_within_domain(target, domain))
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn is_within_domain(target: &str, domain: &str) -> bool {
+    let target = normalize_domain(target);
+    let domain = normalize_domain(domain);
+    target == domain || target.ends_with(&format!(".{domain}"))
+}
+
+impl Default for DefaultResolver {
+    /// System configuration, or trust-dns's public defaults when it cannot
+    /// be read.
+    fn default() -> Self {
+        Self::from_system_conf().unwrap_or_else(|err| {
+            log::warn!("{err}; falling back to default nameservers");
+            Self::new(ResolverConfig::default(), ResolverOpts::default())
+        })
+    }
+}
+
+/// Whether a lookup failed because the name or record type does not exist,
+/// as opposed to no usable answer arriving at all.
+fn is_no_records(err: &ResolveError) -> bool {
+    matches!(
+        err.kind(),
+        ResolveErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain | ResponseCode::NoError,
+            ..
+        }
+    )
+}
+
+fn unavailable(name: &str, err: ResolveError) -> PaymailError {
+    PaymailError::DnsUnavailable(format!("Lookup of {name} failed: {err}"))
+}
+
+fn ttl_until(valid_until: Instant) -> Option<Duration> {
+    Some(valid_until.saturating_duration_since(Instant::now()))
+}
+
 pub async fn resolve_host(domain: &str) -> Result<(String, u16), PaymailError> {
-    DefaultResolver.resolve_host(domain).await
+    DefaultResolver::default().resolve_host_port(domain).await
 }
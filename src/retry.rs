@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::errors::{PaymailError, TimeoutPhase};
+use crate::utils;
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+    pub retryable_statuses: HashSet<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retryable_statuses: HashSet::from([408, 429, 500, 502, 503, 504]),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retryable_statuses(mut self, statuses: impl IntoIterator<Item = u16>) -> Self {
+        self.retryable_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Delay before retry number `retry` (starting at 1), without `Retry-After`.
+    pub fn backoff_delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        let half = delay / 2;
+        half + half.mul_f64(random_unit())
+    }
+
+    /// Delay before retry number `retry`, or `None` when the server's
+    /// `Retry-After` exceeds `max_delay` and the error should be returned.
+    pub(crate) fn delay_for(&self, err: &PaymailError, retry: u32) -> Option<Duration> {
+        match err.http_details().and_then(|d| d.retry_after) {
+            Some(retry_after) if retry_after > self.max_delay => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff_delay(retry)),
+        }
+    }
+
+    /// Non-idempotent calls are only retried when the request provably never
+    /// reached the server (connection refused or timed out while connecting).
+    pub(crate) fn should_retry(&self, err: &PaymailError, idempotent: bool) -> bool {
+        match err {
+            PaymailError::Connection(_) | PaymailError::Timeout(TimeoutPhase::Connect) => true,
+            PaymailError::Timeout(_) => idempotent,
+            PaymailError::HttpError(e) => idempotent && e.is_request(),
+            _ => match err.http_details() {
+                Some(details) => idempotent && self.retryable_statuses.contains(&details.status),
+                None => false,
+            },
+        }
+    }
+}
+
+fn random_unit() -> f64 {
+    (utils::random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -2,15 +2,22 @@ use async_trait::async_trait;
 
 use crate::errors::PaymailError;
 use crate::models::{
-    P2PPaymentDestinationResponse, P2PTxResponse, PaymentDestinationResponse, PkiResponse,
+    P2PPaymentDestinationResponse, P2PTxMetadata, P2PTxResponse, PaymentDestinationResponse,
+    PaymentRequest, PkiResponse,
 };
-use crate::utils;
+use crate::utils::{self, SignatureScheme};
 use serde_json::Value;
 
 #[async_trait]
 pub trait PaymailHandler {
     async fn handle_pki(&self, alias: &str, domain: &str) -> Result<PkiResponse, PaymailError>;
 
+    /// Scheme the default handlers verify sender signatures with. Override
+    /// to return `SignatureScheme::Legacy` only for clients on old releases.
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignatureScheme::default()
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn handle_payment_destination(
         &self,
@@ -23,12 +30,20 @@ pub trait PaymailHandler {
         signature: &str,
         sender_pubkey: &str,
     ) -> Result<PaymentDestinationResponse, PaymailError> {
-        let message = format!(
-            "{sender_handle}|{dt}|{amount}|{purpose}",
-            amount = amount.unwrap_or(0),
-            purpose = purpose.as_deref().unwrap_or("")
-        );
-        if !utils::verify_signature(sender_pubkey, signature, &message)? {
+        let request = PaymentRequest {
+            sender_name: None,
+            sender_handle: sender_handle.to_string(),
+            dt: dt.to_string(),
+            amount,
+            purpose,
+            signature: signature.to_string(),
+        };
+        if !utils::verify_signature_with(
+            sender_pubkey,
+            &request.signature,
+            &request.signable_message(),
+            self.signature_scheme(),
+        )? {
             return Err(PaymailError::InvalidSignature(
                 "Signature verification failed".to_string(),
             ));
@@ -56,19 +71,21 @@ pub trait PaymailHandler {
         })
     }
 
-    #[allow(clippy::too_many_arguments)]
     async fn handle_p2p_tx(
         &self,
         _alias: &str,
         _domain: &str,
         hex: &str,
-        _metadata: Value,
-        reference: &str,
-        signature: &str,
+        metadata: P2PTxMetadata,
+        _reference: &str,
         sender_pubkey: &str,
     ) -> Result<P2PTxResponse, PaymailError> {
-        let message = format!("{hex}|{reference}");
-        if !utils::verify_signature(sender_pubkey, signature, &message)? {
+        let signature = metadata.signature.as_deref().ok_or_else(|| {
+            PaymailError::InvalidSignature("Missing sender signature".to_string())
+        })?;
+        let txid = utils::txid(hex)?;
+        if !utils::verify_signature_with(sender_pubkey, signature, &txid, self.signature_scheme())?
+        {
             return Err(PaymailError::InvalidSignature(
                 "Signature verification failed".to_string(),
             ));
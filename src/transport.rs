@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::errors::{PaymailError, TimeoutPhase};
+
+/// Timeouts of the transports the crate builds itself.
+pub(crate) const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn get(url: &str) -> Self {
+        Self {
+            method: HttpMethod::Get,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn post(url: &str, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            method: HttpMethod::Post,
+            url: url.to_string(),
+            headers: vec![("content-type".to_string(), content_type.to_string())],
+            body: Some(body),
+            timeout: None,
+        }
+    }
+
+    pub fn post_json(url: &str, body: Vec<u8>) -> Self {
+        Self::post(url, "application/json", body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Transports report failures that happened before the request reached the
+/// server as `PaymailError::Connection`, and timeouts as `PaymailError::Timeout`.
+///
+/// Transports that follow redirects must not leave the client's scheme
+/// policy, e.g. by following a redirect to plaintext `http`; either disable
+/// redirects or apply the same rules. `HttpResponse::url` must be the final
+/// URL, and the client rejects responses whose URL the policy does not allow.
+#[async_trait::async_trait]
+pub trait HttpTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, PaymailError>;
+}
+
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, PaymailError> {
+        let mut builder = match request.method {
+            HttpMethod::Get => self.client.get(&request.url),
+            HttpMethod::Post => self.client.post(&request.url),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let resp = builder.send().await.map_err(map_reqwest_error)?;
+        let status = resp.status().as_u16();
+        let url = resp.url().to_string();
+        let headers = resp
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str().to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        let body = resp.bytes().await.map_err(map_reqwest_error)?.to_vec();
+        Ok(HttpResponse {
+            status,
+            url,
+            headers,
+            body,
+        })
+    }
+}
+
+fn map_reqwest_error(e: reqwest::Error) -> PaymailError {
+    if e.is_timeout() && e.is_connect() {
+        PaymailError::Timeout(TimeoutPhase::Connect)
+    } else if e.is_timeout() {
+        PaymailError::Timeout(TimeoutPhase::Endpoint)
+    } else if e.is_connect() {
+        PaymailError::Connection(e.to_string())
+    } else if let Some(err) =
+        std::error::Error::source(&e).and_then(|source| source.downcast_ref::<PaymailError>())
+    {
+        // Raised by our own redirect policy, e.g. `InsecureScheme`.
+        err.duplicate()
+    } else {
+        PaymailError::HttpError(e)
+    }
+}
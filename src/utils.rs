@@ -2,34 +2,142 @@ use crate::errors::PaymailError;
 use base64::Engine;
 use hex;
 use ring::digest::SHA256;
+use ring::rand::{SecureRandom, SystemRandom};
+use ripemd::{Digest, Ripemd160};
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, ecdsa};
 use sv::script::Script;
 
+const BSM_MAGIC: &[u8] = b"Bitcoin Signed Message:\n";
+
+/// How messages are hashed before signing with a recoverable ECDSA signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureScheme {
+    /// Bitcoin Signed Message, as used by paymail sender validation and P2P
+    /// transactions and by every other wallet.
+    #[default]
+    BitcoinSignedMessage,
+    /// Single SHA-256 of the raw message, as produced by earlier releases of
+    /// this crate. Not interoperable; only for peers that still depend on it.
+    Legacy,
+}
+
+impl SignatureScheme {
+    fn digest(self, message: &str) -> Message {
+        match self {
+            SignatureScheme::BitcoinSignedMessage => Message::from_digest(bsm_digest(message)),
+            SignatureScheme::Legacy => Message::from_digest(sha256(message.as_bytes())),
+        }
+    }
+
+    fn accepts_header(self, header: u8) -> bool {
+        match self {
+            // 27–30 uncompressed, 31–34 compressed public key.
+            SignatureScheme::BitcoinSignedMessage => (27..=34).contains(&header),
+            SignatureScheme::Legacy => (31..=34).contains(&header),
+        }
+    }
+}
+
+/// Double SHA-256 of the magic prefix and message, each preceded by its
+/// varint length.
+pub fn bsm_digest(message: &str) -> [u8; 32] {
+    let mut data = Vec::with_capacity(BSM_MAGIC.len() + message.len() + 10);
+    write_varint(&mut data, BSM_MAGIC.len() as u64);
+    data.extend_from_slice(BSM_MAGIC);
+    write_varint(&mut data, message.len() as u64);
+    data.extend_from_slice(message.as_bytes());
+    sha256(&sha256(&data))
+}
+
+fn write_varint(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(ring::digest::digest(&SHA256, data).as_ref());
+    out
+}
+
+/// Signs `message` as a Bitcoin Signed Message, base64-encoded.
 pub fn generate_signature(priv_key: &SecretKey, message: &str) -> Result<String, PaymailError> {
-    let hash = ring::digest::digest(&SHA256, message.as_bytes());
-    let msg_array: [u8; 32] = hash
-        .as_ref()
-        .try_into()
-        .map_err(|_| PaymailError::Other("Invalid hash length".to_string()))?;
-    let msg = Message::from_digest(msg_array);
+    generate_signature_with(priv_key, message, SignatureScheme::default())
+}
+
+pub fn generate_signature_with(
+    priv_key: &SecretKey,
+    message: &str,
+    scheme: SignatureScheme,
+) -> Result<String, PaymailError> {
     let secp = Secp256k1::new();
-    let recoverable_sig = secp.sign_ecdsa_recoverable(msg, priv_key);
+    let recoverable_sig = secp.sign_ecdsa_recoverable(scheme.digest(message), priv_key);
     let (recovery_id, compact) = recoverable_sig.serialize_compact();
     let mut full_sig = [0u8; 65];
+    // Keys are always used compressed, hence 27 + 4.
     full_sig[0] = 31 + i32::from(recovery_id) as u8;
     full_sig[1..].copy_from_slice(&compact);
     Ok(base64::engine::general_purpose::STANDARD.encode(full_sig))
 }
 
+/// Verifies a base64 Bitcoin Signed Message signature against a hex-encoded
+/// public key, compressed or not.
 pub fn verify_signature(
     pub_key_hex: &str,
     signature: &str,
     message: &str,
+) -> Result<bool, PaymailError> {
+    verify_signature_with(pub_key_hex, signature, message, SignatureScheme::default())
+}
+
+pub fn verify_signature_with(
+    pub_key_hex: &str,
+    signature: &str,
+    message: &str,
+    scheme: SignatureScheme,
 ) -> Result<bool, PaymailError> {
     let pub_key_bytes = hex::decode(pub_key_hex).map_err(|e| PaymailError::Other(e.to_string()))?;
     let pub_key =
         PublicKey::from_slice(&pub_key_bytes).map_err(|e| PaymailError::Other(e.to_string()))?;
+    let (compact_sig, _) = decode_signature(signature, scheme)?;
+    let secp = Secp256k1::new();
+    Ok(secp
+        .recover_ecdsa(scheme.digest(message), &compact_sig)
+        .is_ok_and(|recovered| recovered == pub_key))
+}
+
+/// Recovers the signer's public key from a base64 65-byte compact signature,
+/// along with whether the header marks it as compressed.
+pub fn recover_pubkey(
+    signature: &str,
+    message: &str,
+    scheme: SignatureScheme,
+) -> Result<(PublicKey, bool), PaymailError> {
+    let (compact_sig, compressed) = decode_signature(signature, scheme)?;
+    let secp = Secp256k1::new();
+    let pub_key = secp
+        .recover_ecdsa(scheme.digest(message), &compact_sig)
+        .map_err(|e| PaymailError::InvalidSignature(e.to_string()))?;
+    Ok((pub_key, compressed))
+}
 
+fn decode_signature(
+    signature: &str,
+    scheme: SignatureScheme,
+) -> Result<(ecdsa::RecoverableSignature, bool), PaymailError> {
     let sig_bytes = base64::engine::general_purpose::STANDARD
         .decode(signature)
         .map_err(|e| PaymailError::Other(e.to_string()))?;
@@ -39,29 +147,94 @@ pub fn verify_signature(
         ));
     }
     let header = sig_bytes[0];
-    if !(31..=34).contains(&header) {
+    if !scheme.accepts_header(header) {
         return Err(PaymailError::InvalidSignature(
             "Invalid recovery header".to_string(),
         ));
     }
-    let recovery_id = ecdsa::RecoveryId::try_from((header - 31) as i32)
+    let recovery_id = ecdsa::RecoveryId::try_from(i32::from((header - 27) % 4))
         .map_err(|e| PaymailError::Other(e.to_string()))?;
     let compact_sig = ecdsa::RecoverableSignature::from_compact(&sig_bytes[1..], recovery_id)
         .map_err(|e| PaymailError::Other(e.to_string()))?;
-    let standard_sig = compact_sig.to_standard();
+    Ok((compact_sig, header >= 31))
+}
 
-    let hash = ring::digest::digest(&SHA256, message.as_bytes());
-    let msg_array: [u8; 32] = hash
-        .as_ref()
-        .try_into()
-        .map_err(|_| PaymailError::Other("Invalid hash length".to_string()))?;
-    let msg = Message::from_digest(msg_array);
+/// Network an address belongs to, by its base58check version byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
 
-    let secp = Secp256k1::new();
-    Ok(secp.verify_ecdsa(msg, &standard_sig, &pub_key).is_ok())
+impl Network {
+    fn p2pkh_version(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet => 0x6f,
+        }
+    }
+}
+
+/// Base58check P2PKH address of `pub_key`, hashing its compressed or
+/// uncompressed serialisation.
+pub fn p2pkh_address(pub_key: &PublicKey, compressed: bool, network: Network) -> String {
+    let hash = if compressed {
+        hash160(&pub_key.serialize())
+    } else {
+        hash160(&pub_key.serialize_uncompressed())
+    };
+    bs58::encode(hash)
+        .with_check_version(network.p2pkh_version())
+        .into_string()
+}
+
+/// Verifies a Bitcoin Signed Message signature against a mainnet or testnet
+/// P2PKH address, for identities that publish an address instead of a key.
+/// Returns the recovered public key if it matches, `None` otherwise.
+pub fn verify_signature_for_address(
+    address: &str,
+    signature: &str,
+    message: &str,
+) -> Result<Option<PublicKey>, PaymailError> {
+    let decoded = bs58::decode(address)
+        .with_check(None)
+        .into_vec()
+        .map_err(|e| PaymailError::InvalidFormat(format!("Invalid address: {e}")))?;
+    let network = match decoded.first() {
+        Some(0x00) if decoded.len() == 21 => Network::Mainnet,
+        Some(0x6f) if decoded.len() == 21 => Network::Testnet,
+        _ => {
+            return Err(PaymailError::InvalidFormat(
+                "Not a P2PKH address".to_string(),
+            ));
+        }
+    };
+    let (pub_key, compressed) =
+        recover_pubkey(signature, message, SignatureScheme::BitcoinSignedMessage)?;
+    Ok((p2pkh_address(&pub_key, compressed, network) == address).then_some(pub_key))
+}
+
+/// Txid of a hex-encoded transaction: its double SHA-256, byte-reversed.
+pub fn txid(tx_hex: &str) -> Result<String, PaymailError> {
+    let bytes = hex::decode(tx_hex)
+        .map_err(|e| PaymailError::InvalidFormat(format!("Invalid transaction hex: {e}")))?;
+    let mut hash = sha256(&sha256(&bytes));
+    hash.reverse();
+    Ok(hex::encode(hash))
+}
+
+fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(sha256(data)).into()
 }
 
 pub fn parse_script(hex_str: &str) -> Result<Script, PaymailError> {
     let bytes = hex::decode(hex_str).map_err(|e| PaymailError::Other(e.to_string()))?;
     Ok(Script(bytes))
 }
+
+pub(crate) fn random_u64() -> u64 {
+    let mut buf = [0u8; 8];
+    // Only used for jitter and load spreading, so a failed draw is harmless.
+    let _ = SystemRandom::new().fill(&mut buf);
+    u64::from_le_bytes(buf)
+}
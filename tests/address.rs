@@ -0,0 +1,45 @@
+use paymail_rs::PaymailAddress;
+use paymail_rs::errors::PaymailError;
+
+#[test]
+fn test_parses_and_normalizes_domain() {
+    let address: PaymailAddress = "Alice.Smith@Example.COM.".parse().unwrap();
+    assert_eq!(address.alias(), "Alice.Smith");
+    assert_eq!(address.domain(), "example.com");
+    assert_eq!(address.to_string(), "Alice.Smith@example.com");
+
+    let idn: PaymailAddress = "bob@Bücher.example".parse().unwrap();
+    assert_eq!(idn.domain(), "xn--bcher-kva.example");
+}
+
+#[test]
+fn test_rejects_malformed_addresses() {
+    for input in [
+        "example.com",
+        "@example.com",
+        "alice@",
+        "al ice@example.com",
+        "alice@bob@example.com",
+        "alice@exa mple.com",
+        "alice@127.0.0.1",
+        "alice@127.1",
+        "alice@[::1]",
+        "alice@::1",
+    ] {
+        let err = input.parse::<PaymailAddress>().unwrap_err();
+        assert!(matches!(err, PaymailError::InvalidFormat(_)), "{input}");
+    }
+}
+
+#[test]
+fn test_serde_uses_string_form() {
+    let address = PaymailAddress::new("alice", "EXAMPLE.com").unwrap();
+    assert_eq!(
+        serde_json::to_value(&address).unwrap(),
+        serde_json::json!("alice@example.com")
+    );
+
+    let parsed: PaymailAddress = serde_json::from_str("\"alice@Example.com\"").unwrap();
+    assert_eq!(parsed, address);
+    assert!(serde_json::from_str::<PaymailAddress>("\"not a paymail\"").is_err());
+}
@@ -0,0 +1,137 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::net::UdpSocket;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::proto::op::{Message, MessageType, ResponseCode};
+use trust_dns_resolver::proto::rr::rdata::{A, AAAA, SRV};
+use trust_dns_resolver::proto::rr::{Name, RData, Record, RecordType};
+use wiremock::{Request, Respond, ResponseTemplate};
+
+/// Minimal authoritative DNS stand-in answering from an in-memory zone over UDP.
+#[derive(Default)]
+pub struct DnsStandIn {
+    records: HashMap<(String, RecordType), Vec<Record>>,
+    servfail: bool,
+}
+
+impl DnsStandIn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(mut self, name: &str, rdata: RData) -> Self {
+        let name = Name::from_str(&format!("{}.", name.trim_end_matches('.'))).unwrap();
+        let key = (name.to_lowercase().to_string(), rdata.record_type());
+        self.records
+            .entry(key)
+            .or_default()
+            .push(Record::from_rdata(name, 300, rdata));
+        self
+    }
+
+    pub fn srv(self, domain: &str, priority: u16, weight: u16, port: u16, target: &str) -> Self {
+        let name = format!("_bsvalias._tcp.{domain}");
+        let target = Name::from_str(&format!("{}.", target.trim_end_matches('.'))).unwrap();
+        self.add(&name, RData::SRV(SRV::new(priority, weight, port, target)))
+    }
+
+    pub fn a(self, domain: &str, ip: Ipv4Addr) -> Self {
+        self.add(domain, RData::A(A(ip)))
+    }
+
+    pub fn aaaa(self, domain: &str, ip: Ipv6Addr) -> Self {
+        self.add(domain, RData::AAAA(AAAA(ip)))
+    }
+
+    /// Answers every query with SERVFAIL, like a broken upstream.
+    pub fn servfail(mut self) -> Self {
+        self.servfail = true;
+        self
+    }
+
+    pub async fn start(self) -> SocketAddr {
+        self.start_counted().await.0
+    }
+
+    /// Like `start`, also returning the number of queries received so far.
+    pub async fn start_counted(self) -> (SocketAddr, Arc<AtomicUsize>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let zone = Arc::new(self);
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            loop {
+                let Ok((len, peer)) = socket.recv_from(&mut buf).await else {
+                    return;
+                };
+                let Ok(request) = Message::from_vec(&buf[..len]) else {
+                    continue;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = zone.answer(&request);
+                let _ = socket.send_to(&response.to_vec().unwrap(), peer).await;
+            }
+        });
+        (addr, queries)
+    }
+}
+
+/// Serves the zone as an RFC 8484 endpoint when mounted on a wiremock server.
+impl Respond for DnsStandIn {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let Ok(query) = Message::from_vec(&request.body) else {
+            return ResponseTemplate::new(400);
+        };
+        ResponseTemplate::new(200)
+            .insert_header("content-type", "application/dns-message")
+            .set_body_bytes(self.answer(&query).to_vec().unwrap())
+    }
+}
+
+impl DnsStandIn {
+    fn answer(&self, request: &Message) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .set_authoritative(true);
+        let Some(query) = request.queries().first() else {
+            return response.set_response_code(ResponseCode::FormErr).clone();
+        };
+        response.add_query(query.clone());
+        if self.servfail {
+            return response.set_response_code(ResponseCode::ServFail).clone();
+        }
+        let name = query.name().to_lowercase().to_string();
+        match self.records.get(&(name.clone(), query.query_type())) {
+            Some(records) => {
+                response.add_answers(records.iter().cloned());
+            }
+            None if self.records.keys().any(|(n, _)| *n == name) => {}
+            None => {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+        }
+        response
+    }
+}
+
+pub fn resolver_config(addr: SocketAddr) -> (ResolverConfig, ResolverOpts) {
+    let servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+    let config = ResolverConfig::from_parts(None, vec![], servers);
+    let mut opts = ResolverOpts::default();
+    opts.attempts = 1;
+    opts.cache_size = 0;
+    (config, opts)
+}
@@ -1,19 +1,57 @@
 use mockall::mock;
-use paymail_rs::PaymailClient;
-use paymail_rs::resolver::Resolver;
+use paymail_rs::cache::{CachePolicy, CapabilityStore, FileCapabilityStore};
+use paymail_rs::client::PaymailClientBuilder;
+use paymail_rs::errors::{PaymailError, TimeoutPhase};
+use paymail_rs::models::{P2PTxMetadata, P2PTxRequest, PaymentRequest};
+use paymail_rs::resolver::{HostResolver, StaticResolver};
+use paymail_rs::retry::RetryPolicy;
+use paymail_rs::transport::{HttpMethod, HttpRequest, HttpResponse, HttpTransport};
+use paymail_rs::utils;
+use paymail_rs::{PaymailAddress, PaymailClient};
 use secp256k1::SecretKey;
-use std::sync::Arc;
-use wiremock::matchers::{method, path};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wiremock::matchers::{header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 mock! {
     Resolver {}
     #[async_trait::async_trait]
-    impl Resolver for Resolver {
+    impl HostResolver for Resolver {
         async fn resolve_host(&self, domain: &str) -> Result<(String, u16), paymail_rs::errors::PaymailError>;
     }
 }
 
+fn builder_for(mock_server: &MockServer) -> PaymailClientBuilder {
+    let mock_addr = *mock_server.address();
+    let resolver = StaticResolver::new()
+        .with_host("example.com", &mock_addr.ip().to_string(), mock_addr.port())
+        .unwrap();
+    PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .allow_plaintext_host(&mock_addr.ip().to_string())
+        .retry_policy(
+            RetryPolicy::default().backoff(Duration::from_millis(1), Duration::from_millis(10)),
+        )
+}
+
+fn client_for(mock_server: &MockServer) -> PaymailClient {
+    let dummy_priv = SecretKey::from_byte_array([0x01; 32]).unwrap();
+    builder_for(mock_server).build(dummy_priv)
+}
+
+async fn mount_capabilities(mock_server: &MockServer, capabilities: serde_json::Value) {
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": capabilities
+        })))
+        .mount(mock_server)
+        .await;
+}
+
 #[tokio::test]
 async fn test_get_capabilities() {
     let mock_server = MockServer::start().await;
@@ -38,6 +76,7 @@ async fn test_get_capabilities() {
         .and_then(|s| s.split(':').nth(1))
         .and_then(|s| s.trim_end_matches('/').parse::<u16>().ok())
         .unwrap_or(80);
+    let plaintext_host = mock_host.clone();
     mock_resolver
         .expect_resolve_host()
         .with(mockall::predicate::eq("example.com"))
@@ -46,6 +85,7 @@ async fn test_get_capabilities() {
 
     let client = PaymailClient::builder()
         .resolver(Arc::new(mock_resolver))
+        .allow_plaintext_host(&plaintext_host)
         .build(dummy_priv);
 
     Mock::given(method("GET"))
@@ -91,14 +131,16 @@ async fn test_get_pubkey() {
         .and_then(|s| s.split(':').nth(1))
         .and_then(|s| s.trim_end_matches('/').parse::<u16>().ok())
         .unwrap_or(80);
+    let plaintext_host = mock_host.clone();
     mock_resolver
         .expect_resolve_host()
         .with(mockall::predicate::eq("example.com"))
-        .times(2)
+        .times(1)
         .returning(move |_| Ok((mock_host.clone(), mock_port)));
 
     let client = PaymailClient::builder()
         .resolver(Arc::new(mock_resolver))
+        .allow_plaintext_host(&plaintext_host)
         .build(dummy_priv);
 
     // Mock capabilities response
@@ -131,3 +173,1240 @@ async fn test_get_pubkey() {
         .expect("Failed to get pubkey");
     assert_eq!(pubkey, "02abcd1234");
 }
+
+#[tokio::test]
+async fn test_https_is_default_scheme() {
+    let dummy_priv = SecretKey::from_byte_array([0x01; 32]).unwrap();
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "paymail.example.com", 443)
+        .unwrap();
+
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .build(dummy_priv);
+
+    let base_url = client.get_base_url("example.com").await.unwrap();
+    assert_eq!(base_url, "https://paymail.example.com:443");
+}
+
+#[tokio::test]
+async fn test_plaintext_endpoint_rejected_for_unlisted_host() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "http://attacker.example/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+
+    let err = client.get_pubkey("alice@example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::InsecureScheme(_)));
+}
+
+#[tokio::test]
+async fn test_not_found_maps_to_structured_error() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/bob@example.com"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+            "code": "not-found",
+            "message": "Paymail not found"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_pubkey("bob@example.com").await.unwrap_err();
+    let PaymailError::NotFound(details) = err else {
+        panic!("expected NotFound, got {err:?}");
+    };
+    assert_eq!(details.status, 404);
+    assert_eq!(details.capability, "pki");
+    assert!(details.endpoint.ends_with("/id/bob@example.com"));
+    assert_eq!(details.remote.unwrap().code, "not-found");
+}
+
+#[tokio::test]
+async fn test_server_error_maps_to_provider_error() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("upstream unavailable"))
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_capabilities("example.com").await.unwrap_err();
+    let details = err.http_details().expect("expected HTTP details");
+    assert!(matches!(err, PaymailError::ProviderError(_)));
+    assert_eq!(details.status, 503);
+    assert_eq!(details.body, "upstream unavailable");
+    assert!(details.remote.is_none());
+}
+
+#[tokio::test]
+async fn test_idempotent_call_retries_transient_failures() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "2a40af698840": "/p2p/{alias}@{domain.tld}/destination" }),
+    )
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/p2p/carol@example.com/destination"))
+        .respond_with(ResponseTemplate::new(502))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/p2p/carol@example.com/destination"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "outputs": [{ "script": "76a914deadbeef88ac", "satoshis": 1000 }],
+            "reference": "ref-1"
+        })))
+        .with_priority(2)
+        .mount(&mock_server)
+        .await;
+
+    let resp = client
+        .get_p2p_payment_destination("carol@example.com", 1000)
+        .await
+        .expect("retry should succeed");
+    assert_eq!(resp.reference, "ref-1");
+}
+
+#[tokio::test]
+async fn test_send_p2p_tx_does_not_retry_server_errors() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "5f1323cddf31": "/p2p/{alias}@{domain.tld}/tx" }),
+    )
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/p2p/carol@example.com/tx"))
+        .respond_with(ResponseTemplate::new(502))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = client
+        .send_p2p_tx("carol@example.com", "00", P2PTxMetadata::default(), "ref-1")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::ProviderError(_)));
+}
+
+/// Serves the capabilities document and times out connecting for every POST.
+#[derive(Default)]
+struct ConnectTimeoutTransport {
+    posts: Mutex<u32>,
+}
+
+#[async_trait::async_trait]
+impl HttpTransport for ConnectTimeoutTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, PaymailError> {
+        if request.method == HttpMethod::Post {
+            *self.posts.lock().unwrap() += 1;
+            return Err(PaymailError::Timeout(TimeoutPhase::Connect));
+        }
+        let body = serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "5f1323cddf31": "/p2p/{alias}@{domain.tld}/tx" }
+        });
+        Ok(HttpResponse {
+            status: 200,
+            url: request.url,
+            headers: Vec::new(),
+            body: serde_json::to_vec(&body).unwrap(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_send_p2p_tx_retries_connect_timeouts() {
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "paymail.example.com", 443)
+        .unwrap();
+    let transport = Arc::new(ConnectTimeoutTransport::default());
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .transport(transport.clone())
+        .retry_policy(
+            RetryPolicy::default().backoff(Duration::from_millis(1), Duration::from_millis(10)),
+        )
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    let err = client
+        .send_p2p_tx("carol@example.com", "00", P2PTxMetadata::default(), "ref-1")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::Timeout(TimeoutPhase::Connect)));
+    assert_eq!(*transport.posts.lock().unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_send_p2p_tx_signs_txid_in_metadata() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "5f1323cddf31": "/p2p/{alias}@{domain.tld}/tx" }),
+    )
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/p2p/carol@example.com/tx"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "txid": "ignored"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let metadata = P2PTxMetadata {
+        sender: Some("alice@example.com".to_string()),
+        note: Some("hi".to_string()),
+        ..Default::default()
+    };
+    client
+        .send_p2p_tx("carol@example.com", "0100", metadata, "ref-1")
+        .await
+        .unwrap();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let sent: P2PTxRequest = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(sent.reference, "ref-1");
+    assert_eq!(sent.metadata.sender.as_deref(), Some("alice@example.com"));
+    assert_eq!(sent.metadata.note.as_deref(), Some("hi"));
+    let txid = utils::txid("0100").unwrap();
+    assert!(
+        utils::verify_signature(
+            sent.metadata.pubkey.as_deref().unwrap(),
+            sent.metadata.signature.as_deref().unwrap(),
+            &txid,
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_retry_backoff_is_capped() {
+    let policy = RetryPolicy::default()
+        .backoff(Duration::from_millis(100), Duration::from_millis(250))
+        .jitter(false);
+    assert_eq!(policy.backoff_delay(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff_delay(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff_delay(5), Duration::from_millis(250));
+}
+
+#[tokio::test]
+async fn test_long_retry_after_is_not_waited_out() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "120"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_capabilities("example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::RateLimited(_)));
+}
+
+#[tokio::test]
+async fn test_retry_past_deadline_returns_last_error() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .retry_policy(
+            RetryPolicy::default().backoff(Duration::from_millis(1), Duration::from_secs(5)),
+        )
+        .deadline(Duration::from_millis(500))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "2"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_capabilities("example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::ProviderError(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_request_timeout_reports_endpoint_phase() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .request_timeout(Duration::from_millis(100))
+        .retry_policy(RetryPolicy::none())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_pubkey("alice@example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::Timeout(TimeoutPhase::Endpoint)));
+}
+
+#[tokio::test]
+async fn test_deadline_covers_capability_discovery() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .deadline(Duration::from_millis(200))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_pubkey("alice@example.com").await.unwrap_err();
+    assert!(matches!(
+        err,
+        PaymailError::Timeout(TimeoutPhase::Capabilities)
+    ));
+}
+
+struct StubTransport {
+    routes: HashMap<String, serde_json::Value>,
+    seen: Mutex<Vec<(HttpMethod, String)>>,
+}
+
+#[async_trait::async_trait]
+impl HttpTransport for StubTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, PaymailError> {
+        self.seen
+            .lock()
+            .unwrap()
+            .push((request.method, request.url.clone()));
+        let (status, body) = match self.routes.get(&request.url) {
+            Some(body) => (200, serde_json::to_vec(body).unwrap()),
+            None => (404, Vec::new()),
+        };
+        Ok(HttpResponse {
+            status,
+            url: request.url,
+            headers: Vec::new(),
+            body,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_transport_is_used_for_all_calls() {
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "paymail.example.com", 443)
+        .unwrap();
+    let transport = Arc::new(StubTransport {
+        routes: HashMap::from([
+            (
+                "https://paymail.example.com:443/.well-known/bsvalias".to_string(),
+                serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+                }),
+            ),
+            (
+                "https://paymail.example.com:443/id/alice@example.com".to_string(),
+                serde_json::json!({
+                    "bsvalias": "1.0",
+                    "handle": "alice@example.com",
+                    "pubkey": "02abcd1234"
+                }),
+            ),
+        ]),
+        seen: Mutex::new(Vec::new()),
+    });
+
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .transport(transport.clone())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    let pubkey = client.get_pubkey("alice@example.com").await.unwrap();
+    assert_eq!(pubkey, "02abcd1234");
+    let seen = transport.seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert!(seen.iter().all(|(m, _)| *m == HttpMethod::Get));
+}
+
+#[tokio::test]
+async fn test_concurrent_lookups_share_one_fetch() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(200))
+                .set_body_json(serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": {}
+                })),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let lookups = (0..10).map(|_| {
+        let client = client.clone();
+        tokio::spawn(async move { client.get_capabilities("example.com").await })
+    });
+    for lookup in lookups.collect::<Vec<_>>() {
+        lookup.await.unwrap().expect("lookup should succeed");
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_lookups_share_one_failure() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .retry_policy(RetryPolicy::none())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(503).set_delay(Duration::from_millis(200)))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let lookups = (0..10).map(|_| {
+        let client = client.clone();
+        tokio::spawn(async move { client.get_capabilities("example.com").await })
+    });
+    for lookup in lookups.collect::<Vec<_>>() {
+        let err = lookup.await.unwrap().unwrap_err();
+        assert!(matches!(err, PaymailError::ProviderError(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_follower_takes_over_from_cancelled_lookup() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(200))
+                .set_body_json(serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": {}
+                })),
+        )
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let leader = {
+        let client = client.clone();
+        tokio::spawn(async move { client.get_capabilities("example.com").await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let follower = {
+        let client = client.clone();
+        tokio::spawn(async move { client.get_capabilities("example.com").await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    leader.abort();
+
+    follower
+        .await
+        .unwrap()
+        .expect("follower should fetch once the leader is gone");
+}
+
+#[tokio::test]
+async fn test_slow_domain_does_not_block_other_domains() {
+    let slow_server = MockServer::start().await;
+    let fast_server = MockServer::start().await;
+    let slow_addr = *slow_server.address();
+    let fast_addr = *fast_server.address();
+    let resolver = StaticResolver::new()
+        .with_host(
+            "slow.example",
+            &slow_addr.ip().to_string(),
+            slow_addr.port(),
+        )
+        .unwrap()
+        .with_host(
+            "fast.example",
+            &fast_addr.ip().to_string(),
+            fast_addr.port(),
+        )
+        .unwrap();
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .allow_plaintext_host(&fast_addr.ip().to_string())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&slow_server)
+        .await;
+    mount_capabilities(&fast_server, serde_json::json!({})).await;
+
+    let slow_client = client.clone();
+    let slow = tokio::spawn(async move { slow_client.get_capabilities("slow.example").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let fast = tokio::time::timeout(
+        Duration::from_secs(1),
+        client.get_capabilities("fast.example"),
+    )
+    .await
+    .expect("fast domain must not wait for slow domain");
+    assert!(fast.is_ok());
+    slow.abort();
+}
+
+#[tokio::test]
+async fn test_file_store_is_shared_between_clients() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("paymail-rs-store-{}", std::process::id()));
+    let store = Arc::new(FileCapabilityStore::new(&dir));
+    let dummy_priv = SecretKey::from_byte_array([0x01; 32]).unwrap();
+
+    let first = builder_for(&mock_server)
+        .capability_store(store.clone())
+        .build(dummy_priv);
+    first.get_capabilities("example.com").await.unwrap();
+
+    let second = builder_for(&mock_server)
+        .capability_store(Arc::new(FileCapabilityStore::new(&dir)))
+        .build(dummy_priv);
+    let caps = second.get_capabilities("example.com").await.unwrap();
+    assert!(caps.capabilities.contains_key("pki"));
+
+    let entry = store.get("example.com").await.unwrap().unwrap();
+    assert!(entry.is_fresh());
+    store.remove("example.com").await.unwrap();
+    assert!(store.get("example.com").await.unwrap().is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_capabilities_revalidate_with_etag() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .min_cache_ttl(Duration::ZERO)
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304).insert_header("Cache-Control", "max-age=0"))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Cache-Control", "max-age=0")
+                .insert_header("ETag", "\"v1\"")
+                .set_body_json(serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+                })),
+        )
+        .with_priority(2)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    client.get_capabilities("example.com").await.unwrap();
+    let caps = client.get_capabilities("example.com").await.unwrap();
+    assert!(caps.capabilities.contains_key("pki"));
+}
+
+#[tokio::test]
+async fn test_not_modified_response_updates_validators() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .min_cache_ttl(Duration::ZERO)
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .and(header("If-None-Match", "\"v2\""))
+        .respond_with(ResponseTemplate::new(304).insert_header("Cache-Control", "max-age=0"))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(
+            ResponseTemplate::new(304)
+                .insert_header("Cache-Control", "max-age=0")
+                .insert_header("ETag", "\"v2\""),
+        )
+        .with_priority(2)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Cache-Control", "max-age=0")
+                .insert_header("ETag", "\"v1\"")
+                .set_body_json(serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+                })),
+        )
+        .with_priority(3)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..3 {
+        let caps = client.get_capabilities("example.com").await.unwrap();
+        assert!(caps.capabilities.contains_key("pki"));
+    }
+}
+
+#[tokio::test]
+async fn test_max_age_is_clamped_to_minimum_ttl() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .min_cache_ttl(Duration::from_secs(60))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Cache-Control", "max-age=1")
+                .set_body_json(serde_json::json!({
+                    "bsvalias": "1.0",
+                    "capabilities": {}
+                })),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    client.get_capabilities("example.com").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    client.get_capabilities("example.com").await.unwrap();
+}
+
+#[test]
+fn test_cache_policy_reads_response_headers() {
+    let policy = CachePolicy::default();
+    let response = |headers: &[(&str, &str)]| HttpResponse {
+        status: 200,
+        url: "https://example.com/.well-known/bsvalias".to_string(),
+        headers: headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        body: Vec::new(),
+    };
+    assert_eq!(
+        policy.ttl_for(&response(&[("Cache-Control", "no-store")])),
+        None
+    );
+    assert_eq!(
+        policy.ttl_for(&response(&[("Cache-Control", "public, max-age=600")])),
+        Some(Duration::from_secs(600))
+    );
+    assert_eq!(
+        policy.ttl_for(&response(&[("Cache-Control", "max-age=999999")])),
+        Some(policy.max_ttl)
+    );
+    assert_eq!(
+        policy.ttl_for(&response(&[
+            ("Date", "Wed, 21 Oct 2026 07:28:00 GMT"),
+            ("Expires", "Wed, 21 Oct 2026 07:38:00 GMT"),
+        ])),
+        Some(Duration::from_secs(600))
+    );
+    assert_eq!(policy.ttl_for(&response(&[])), Some(policy.default_ttl));
+}
+
+#[tokio::test]
+async fn test_failed_lookups_are_negatively_cached_until_purged() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(404))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let first = client.get_capabilities("example.com").await.unwrap_err();
+    let second = client.get_capabilities("example.com").await.unwrap_err();
+    assert!(matches!(first, PaymailError::NotFound(_)));
+    assert!(matches!(second, PaymailError::NotFound(_)));
+
+    mount_capabilities(&mock_server, serde_json::json!({})).await;
+    client.purge_domain("example.com").await.unwrap();
+    client
+        .get_capabilities("example.com")
+        .await
+        .expect("purged domain should be fetched again");
+}
+
+#[tokio::test]
+async fn test_invalid_capability_document_is_reported() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>parked</html>"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..2 {
+        let err = client.get_capabilities("example.com").await.unwrap_err();
+        assert!(matches!(err, PaymailError::InvalidCapabilities(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_stale_capabilities_served_while_refreshing() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .cache_ttl(Duration::ZERO)
+        .min_cache_ttl(Duration::ZERO)
+        .stale_while_revalidate(Duration::from_secs(60))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(&mock_server, serde_json::json!({ "pki": "/v1/{alias}" })).await;
+    client.get_capabilities("example.com").await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/v2/{alias}" }
+        })))
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    let stale = client.get_capabilities("example.com").await.unwrap();
+    assert_eq!(stale.capabilities["pki"], "/v1/{alias}");
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let refreshed = client.get_capabilities("example.com").await.unwrap();
+    assert_eq!(refreshed.capabilities["pki"], "/v2/{alias}");
+}
+
+#[tokio::test]
+async fn test_stale_capabilities_served_when_provider_down() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .cache_ttl(Duration::ZERO)
+        .min_cache_ttl(Duration::ZERO)
+        .stale_while_revalidate(Duration::from_secs(60))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(&mock_server, serde_json::json!({ "pki": "/v1/{alias}" })).await;
+    client.get_capabilities("example.com").await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..3 {
+        let caps = client.get_capabilities("example.com").await.unwrap();
+        assert_eq!(caps.capabilities["pki"], "/v1/{alias}");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_warm_call_skips_dns_and_capability_fetch() {
+    let mock_server = MockServer::start().await;
+    let mock_addr = *mock_server.address();
+    let mut mock_resolver = MockResolver::new();
+    mock_resolver
+        .expect_resolve_host()
+        .times(1)
+        .returning(move |_| Ok((mock_addr.ip().to_string(), mock_addr.port())));
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(mock_resolver))
+        .allow_plaintext_host(&mock_addr.ip().to_string())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..3 {
+        client.get_pubkey("alice@example.com").await.unwrap();
+    }
+    client.get_base_url("example.com").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unreachable_srv_target_fails_over_to_next() {
+    let mock_server = MockServer::start().await;
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    // Reserve a port and release it so nothing is listening there.
+    let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mock_addr = *mock_server.address();
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "127.0.0.1", closed_port)
+        .unwrap()
+        .with_host("example.com", &mock_addr.ip().to_string(), mock_addr.port())
+        .unwrap();
+
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .allow_plaintext_host("127.0.0.1")
+        .retry_policy(RetryPolicy::none())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    let capabilities = client.get_capabilities("example.com").await.unwrap();
+    assert!(capabilities.capabilities.contains_key("pki"));
+}
+
+#[tokio::test]
+async fn test_endpoint_call_fails_over_when_capability_host_goes_down() {
+    // Built rather than taken from the pool, so dropping it closes the port.
+    let primary = MockServer::builder().start().await;
+    let secondary = MockServer::start().await;
+    mount_capabilities(
+        &primary,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .expect(1)
+        .mount(&secondary)
+        .await;
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "127.0.0.1", primary.address().port())
+        .unwrap()
+        .with_host("example.com", "127.0.0.1", secondary.address().port())
+        .unwrap();
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .allow_plaintext_host("127.0.0.1")
+        .retry_policy(
+            RetryPolicy::default().backoff(Duration::from_millis(1), Duration::from_millis(10)),
+        )
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    client.get_capabilities("example.com").await.unwrap();
+    // The pooled connection to `primary` breaks and the retry finds its port
+    // closed, so the call moves on to `secondary`.
+    drop(primary);
+
+    let pubkey = client.get_pubkey("alice@example.com").await.unwrap();
+    assert_eq!(pubkey, "02abcd1234");
+}
+
+#[tokio::test]
+async fn test_capabilities_published_by_brfc_id_only() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({
+            "0c4339ef99c2": "/id/{alias}@{domain.tld}",
+            "759684b1a19a": "/address/{alias}@{domain.tld}"
+        }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/address/alice@example.com"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "output": "76a914deadbeef88ac" })),
+        )
+        .mount(&mock_server)
+        .await;
+
+    assert_eq!(
+        client.get_pubkey("alice@example.com").await.unwrap(),
+        "02abcd1234"
+    );
+    let request = PaymentRequest {
+        sender_name: None,
+        sender_handle: "bob@example.com".to_string(),
+        dt: String::new(),
+        amount: Some(1000),
+        purpose: None,
+        signature: String::new(),
+    };
+    assert_eq!(
+        client
+            .get_payment_destination("alice@example.com", request)
+            .await
+            .unwrap(),
+        "76a914deadbeef88ac"
+    );
+    // Extensions looked up by alias resolve to the published BRFC ID too.
+    let pki = client
+        .call_extension("alice@example.com", "pki", None)
+        .await
+        .unwrap();
+    assert_eq!(pki["pubkey"], "02abcd1234");
+}
+
+#[tokio::test]
+async fn test_capabilities_published_by_alias_only() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let pki = client
+        .call_extension("alice@example.com", "0c4339ef99c2", None)
+        .await
+        .unwrap();
+    assert_eq!(pki["pubkey"], "02abcd1234");
+}
+
+#[tokio::test]
+async fn test_client_accepts_paymail_addresses_and_normalizes_strings() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    mount_capabilities(
+        &mock_server,
+        serde_json::json!({ "pki": "/id/{alias}@{domain.tld}" }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let address = PaymailAddress::new("alice", "example.com").unwrap();
+    assert_eq!(client.get_pubkey(&address).await.unwrap(), "02abcd1234");
+    assert_eq!(
+        client.get_pubkey("alice@EXAMPLE.com.").await.unwrap(),
+        "02abcd1234"
+    );
+    let err = client.get_pubkey("alice@127.0.0.1").await.unwrap_err();
+    assert!(matches!(err, PaymailError::InvalidFormat(_)));
+}
+
+#[tokio::test]
+async fn test_domain_arguments_share_the_address_cache_key() {
+    let mock_server = MockServer::start().await;
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+        })))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "handle": "alice@example.com",
+            "pubkey": "02abcd1234"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    client.get_capabilities("Example.COM.").await.unwrap();
+    client.get_pubkey("alice@example.com").await.unwrap();
+    client.purge_domain("EXAMPLE.com").await.unwrap();
+    client.get_capabilities("example.com").await.unwrap();
+
+    let err = client.get_capabilities("127.0.0.1").await.unwrap_err();
+    assert!(matches!(err, PaymailError::InvalidFormat(_)));
+}
+
+#[tokio::test]
+async fn test_unbounded_negative_cache_ttl_does_not_overflow() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    let client = builder_for(&mock_server)
+        .negative_cache_ttl(Duration::MAX)
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    for _ in 0..2 {
+        let err = client.get_capabilities("example.com").await.unwrap_err();
+        assert!(matches!(err, PaymailError::NotFound(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_unbounded_cache_ttl_does_not_overflow() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": {}
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    let dir = std::env::temp_dir().join(format!("paymail-rs-forever-{}", std::process::id()));
+    let client = builder_for(&mock_server)
+        .max_cache_ttl(Duration::MAX)
+        .cache_ttl(Duration::MAX)
+        .capability_store(Arc::new(FileCapabilityStore::new(&dir)))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    client.get_capabilities("example.com").await.unwrap();
+    client.get_capabilities("example.com").await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_unbounded_staleness_does_not_overflow() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .cache_ttl(Duration::ZERO)
+        .min_cache_ttl(Duration::ZERO)
+        .stale_while_revalidate(Duration::MAX)
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(&mock_server, serde_json::json!({ "pki": "/v1/{alias}" })).await;
+    client.get_capabilities("example.com").await.unwrap();
+
+    let stale = client.get_capabilities("example.com").await.unwrap();
+    assert_eq!(stale.capabilities["pki"], "/v1/{alias}");
+}
+
+#[tokio::test]
+async fn test_failed_refresh_backs_off() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .retry_policy(RetryPolicy::none())
+        .cache_ttl(Duration::ZERO)
+        .min_cache_ttl(Duration::ZERO)
+        .stale_while_revalidate(Duration::from_secs(60))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(&mock_server, serde_json::json!({ "pki": "/v1/{alias}" })).await;
+    client.get_capabilities("example.com").await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..3 {
+        client.get_capabilities("example.com").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_transient_dns_failures_are_not_cached() {
+    let mut mock_resolver = MockResolver::new();
+    mock_resolver
+        .expect_resolve_host()
+        .times(2)
+        .returning(|_| Err(PaymailError::DnsUnavailable("SERVFAIL".to_string())));
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(mock_resolver))
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    for _ in 0..2 {
+        let err = client.get_capabilities("example.com").await.unwrap_err();
+        assert!(matches!(err, PaymailError::DnsUnavailable(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_redirects_follow_the_scheme_policy() {
+    let mock_server = MockServer::start().await;
+    let port = mock_server.address().port();
+    let client = client_for(&mock_server);
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(302).insert_header("location", "/bsvalias-moved"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/bsvalias-moved"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+        })))
+        .mount(&mock_server)
+        .await;
+    // Plaintext is only allowed for the mock server's IP, not for `localhost`.
+    Mock::given(method("GET"))
+        .and(path("/id/alice@example.com"))
+        .respond_with(
+            ResponseTemplate::new(307)
+                .insert_header("location", format!("http://localhost:{port}/id/plain")),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/id/plain"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    client.get_capabilities("example.com").await.unwrap();
+    let err = client.get_pubkey("alice@example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::InsecureScheme(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_caller_client_redirects_are_checked_against_the_scheme_policy() {
+    let mock_server = MockServer::start().await;
+    let port = mock_server.address().port();
+    let client = builder_for(&mock_server)
+        .http_client(reqwest::Client::new())
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    Mock::given(method("GET"))
+        .and(path("/.well-known/bsvalias"))
+        .respond_with(ResponseTemplate::new(308).insert_header(
+            "location",
+            format!("http://localhost:{port}/bsvalias-plain"),
+        ))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/bsvalias-plain"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bsvalias": "1.0",
+            "capabilities": { "pki": "/id/{alias}@{domain.tld}" }
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let err = client.get_capabilities("example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::InsecureScheme(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_ipv6_hosts_are_bracketed_in_base_urls() {
+    let resolver = StaticResolver::new()
+        .with_host("example.com", "::1", 8443)
+        .unwrap()
+        .with_host("example.org", "2001:db8::1", 443)
+        .unwrap();
+    let client = PaymailClient::builder()
+        .resolver(Arc::new(resolver))
+        .allow_plaintext_host("[::1]")
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+
+    assert_eq!(
+        client.get_base_url("example.com").await.unwrap(),
+        "http://[::1]:8443"
+    );
+    assert_eq!(
+        client.get_base_url("example.org").await.unwrap(),
+        "https://[2001:db8::1]:443"
+    );
+}
+
+#[tokio::test]
+async fn test_unbounded_deadline_does_not_overflow() {
+    let mock_server = MockServer::start().await;
+    let client = builder_for(&mock_server)
+        .deadline(Duration::MAX)
+        .build(SecretKey::from_byte_array([0x01; 32]).unwrap());
+    mount_capabilities(&mock_server, serde_json::json!({})).await;
+
+    client.get_capabilities("example.com").await.unwrap();
+}
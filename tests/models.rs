@@ -0,0 +1,258 @@
+use paymail_rs::models::{
+    Brfc, Capabilities, ErrorResponse, P2PPaymentDestinationRequest, P2PPaymentDestinationResponse,
+    P2PTxMetadata, P2PTxRequest, P2PTxResponse, PaymentDestinationResponse, PaymentRequest,
+    PkiResponse, UnknownFieldPolicy,
+};
+use serde_json::json;
+
+#[test]
+fn test_payment_request_uses_spec_field_names() {
+    let request = PaymentRequest {
+        sender_name: Some("Alice".to_string()),
+        sender_handle: "alice@example.com".to_string(),
+        dt: "2024-01-01T00:00:00.000Z".to_string(),
+        amount: Some(550),
+        purpose: Some("Coffee".to_string()),
+        signature: "H+sig".to_string(),
+    };
+
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        json!({
+            "senderName": "Alice",
+            "senderHandle": "alice@example.com",
+            "dt": "2024-01-01T00:00:00.000Z",
+            "amount": 550,
+            "purpose": "Coffee",
+            "signature": "H+sig"
+        })
+    );
+}
+
+#[test]
+fn test_payment_request_omits_absent_optionals() {
+    let request = PaymentRequest {
+        sender_name: None,
+        sender_handle: "alice@example.com".to_string(),
+        dt: "2024-01-01T00:00:00.000Z".to_string(),
+        amount: None,
+        purpose: None,
+        signature: "H+sig".to_string(),
+    };
+
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        json!({
+            "senderHandle": "alice@example.com",
+            "dt": "2024-01-01T00:00:00.000Z",
+            "signature": "H+sig"
+        })
+    );
+}
+
+#[test]
+fn test_p2p_requests_match_spec() {
+    let destination = P2PPaymentDestinationRequest { satoshis: 1000 };
+    assert_eq!(
+        serde_json::to_value(&destination).unwrap(),
+        json!({ "satoshis": 1000 })
+    );
+
+    let tx = P2PTxRequest {
+        hex: "0100".to_string(),
+        metadata: P2PTxMetadata {
+            sender: Some("alice@example.com".to_string()),
+            pubkey: Some("02abcd".to_string()),
+            signature: Some("H+sig".to_string()),
+            note: Some("hi".to_string()),
+        },
+        reference: "ref-1".to_string(),
+    };
+    assert_eq!(
+        serde_json::to_value(&tx).unwrap(),
+        json!({
+            "hex": "0100",
+            "metadata": {
+                "sender": "alice@example.com",
+                "pubkey": "02abcd",
+                "signature": "H+sig",
+                "note": "hi"
+            },
+            "reference": "ref-1"
+        })
+    );
+}
+
+#[test]
+fn test_responses_parse_spec_documents() {
+    let capabilities: Capabilities = serde_json::from_value(json!({
+        "bsvalias": "1.0",
+        "capabilities": { "pki": "https://example.com/id/{alias}@{domain.tld}" }
+    }))
+    .unwrap();
+    assert_eq!(capabilities.bsvalias, "1.0");
+    assert_eq!(
+        capabilities.capabilities["pki"],
+        "https://example.com/id/{alias}@{domain.tld}"
+    );
+
+    let pki: PkiResponse = serde_json::from_value(json!({
+        "bsvalias": "1.0",
+        "handle": "alice@example.com",
+        "pubkey": "02abcd"
+    }))
+    .unwrap();
+    assert_eq!(
+        (
+            pki.bsvalias.as_str(),
+            pki.handle.as_str(),
+            pki.pubkey.as_str()
+        ),
+        ("1.0", "alice@example.com", "02abcd")
+    );
+
+    let destination: PaymentDestinationResponse =
+        serde_json::from_value(json!({ "output": "76a914deadbeef88ac" })).unwrap();
+    assert_eq!(destination.output, "76a914deadbeef88ac");
+
+    let p2p: P2PPaymentDestinationResponse = serde_json::from_value(json!({
+        "outputs": [{ "script": "76a914deadbeef88ac", "satoshis": 1000 }],
+        "reference": "ref-1"
+    }))
+    .unwrap();
+    assert_eq!(p2p.outputs[0]["satoshis"], 1000);
+    assert_eq!(p2p.reference, "ref-1");
+
+    let tx: P2PTxResponse = serde_json::from_value(json!({ "txid": "abcd" })).unwrap();
+    assert_eq!((tx.txid.as_str(), tx.note), ("abcd", None));
+
+    let error: ErrorResponse =
+        serde_json::from_value(json!({ "code": "not-found", "message": "Unknown alias" })).unwrap();
+    assert_eq!(
+        error,
+        ErrorResponse {
+            code: "not-found".to_string(),
+            message: "Unknown alias".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_every_model_round_trips() {
+    fn round_trip<T>(json: serde_json::Value)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let parsed: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        let reparsed: T = serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+        assert_eq!(reparsed, parsed);
+    }
+
+    round_trip::<Capabilities>(json!({
+        "bsvalias": "1.0",
+        "capabilities": { "pki": "/id/{alias}@{domain.tld}", "6745385c3fc0": false }
+    }));
+    round_trip::<ErrorResponse>(json!({ "code": "not-found", "message": "Unknown alias" }));
+    round_trip::<PaymentRequest>(json!({
+        "senderName": "Alice",
+        "senderHandle": "alice@example.com",
+        "dt": "2024-01-01T00:00:00.000Z",
+        "amount": 550,
+        "signature": "H+sig"
+    }));
+    round_trip::<PaymentDestinationResponse>(json!({ "output": "76a914deadbeef88ac" }));
+    round_trip::<PkiResponse>(json!({
+        "bsvalias": "1.0",
+        "handle": "alice@example.com",
+        "pubkey": "02abcd"
+    }));
+    round_trip::<P2PPaymentDestinationRequest>(json!({ "satoshis": 1000 }));
+    round_trip::<P2PPaymentDestinationResponse>(json!({
+        "outputs": [{ "script": "76a914deadbeef88ac", "satoshis": 1000 }],
+        "reference": "ref-1"
+    }));
+    round_trip::<P2PTxRequest>(json!({
+        "hex": "0100",
+        "metadata": { "sender": "alice@example.com", "signature": "H+sig" },
+        "reference": "ref-1"
+    }));
+    round_trip::<P2PTxResponse>(json!({ "txid": "abcd" }));
+    round_trip::<P2PTxResponse>(json!({ "txid": "abcd", "note": "thanks" }));
+}
+
+#[test]
+fn test_unknown_field_policy() {
+    let body = br#"{"bsvalias":"1.0","handle":"alice@example.com","pubkey":"02abcd","extra":1}"#;
+
+    let lenient: PkiResponse = UnknownFieldPolicy::Lenient.from_slice(body).unwrap();
+    assert_eq!(lenient.pubkey, "02abcd");
+    let err = UnknownFieldPolicy::Strict
+        .from_slice::<PkiResponse>(body)
+        .unwrap_err();
+    assert!(err.to_string().contains("extra"));
+
+    // Explicit nulls for optional fields are fine, nested ones included.
+    let tx: P2PTxResponse = UnknownFieldPolicy::Strict
+        .from_slice(br#"{"txid":"abcd","note":null}"#)
+        .unwrap();
+    assert_eq!(tx.note, None);
+    let request: P2PTxRequest = UnknownFieldPolicy::Strict
+        .from_slice(br#"{"hex":"00","metadata":{"sender":"a@b.c","note":null},"reference":"r"}"#)
+        .unwrap();
+    assert_eq!(request.metadata.note, None);
+}
+
+#[test]
+fn test_typed_capability_accessors() {
+    let capabilities: Capabilities = serde_json::from_value(json!({
+        "bsvalias": "1.0",
+        "capabilities": {
+            "pki": "https://example.com/id/{alias}@{domain.tld}",
+            "759684b1a19a": "https://example.com/address/{alias}@{domain.tld}",
+            "6745385c3fc0": true,
+            "2a40af698840": "https://example.com/p2p/{alias}@{domain.tld}",
+            "abcdef123456": { "custom": [1, 2, 3] }
+        }
+    }))
+    .unwrap();
+
+    assert_eq!(
+        capabilities.template(Brfc::Pki),
+        Some("https://example.com/id/{alias}@{domain.tld}")
+    );
+    assert_eq!(
+        capabilities.template(Brfc::PaymentDestination),
+        Some("https://example.com/address/{alias}@{domain.tld}")
+    );
+    assert!(capabilities.template(Brfc::P2pPaymentDestination).is_some());
+    assert_eq!(capabilities.template(Brfc::Beef), None);
+    assert!(capabilities.flag(Brfc::SenderValidation));
+    assert!(!capabilities.flag(Brfc::Pike));
+
+    let extensions: Vec<_> = capabilities.extensions().collect();
+    assert_eq!(
+        extensions,
+        vec![("abcdef123456", &json!({ "custom": [1, 2, 3] }))]
+    );
+    let reparsed: Capabilities =
+        serde_json::from_value(serde_json::to_value(&capabilities).unwrap()).unwrap();
+    assert_eq!(reparsed, capabilities);
+}
+
+#[test]
+fn test_brfc_ids_and_aliases() {
+    assert_eq!(Brfc::from_key("0c4339ef99c2"), Some(Brfc::Pki));
+    assert_eq!(Brfc::from_key("pki"), Some(Brfc::Pki));
+    assert_eq!(
+        Brfc::from_key("5f1323cddf31"),
+        Some(Brfc::P2pReceiveTransaction)
+    );
+    assert_eq!(Brfc::from_key("8c4ed5ef8ace"), Some(Brfc::Pike));
+    assert_eq!(Brfc::from_key("unknown"), None);
+    assert_eq!(Brfc::Beef.name(), "5c55a7fdb7bb");
+    assert_eq!(Brfc::VerifyPublicKey.name(), "verifyPubKey");
+    for brfc in Brfc::ALL {
+        assert_eq!(Brfc::from_key(brfc.id()), Some(brfc));
+    }
+}
@@ -0,0 +1,376 @@
+mod common;
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use common::{DnsStandIn, resolver_config};
+use paymail_rs::errors::PaymailError;
+use paymail_rs::resolver::{
+    ChainResolver, DefaultResolver, DohResolver, EndpointSource, HostResolver, Resolver,
+    SrvTargetPolicy, StaticResolver,
+};
+use trust_dns_resolver::TokioAsyncResolver;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn resolver_for(zone: DnsStandIn) -> DefaultResolver {
+    let addr = zone.start().await;
+    let (config, opts) = resolver_config(addr);
+    DefaultResolver::new(config, opts)
+}
+
+#[tokio::test]
+async fn test_srv_record_is_preferred() {
+    let resolver = resolver_for(
+        DnsStandIn::new()
+            .srv("srv.test", 10, 10, 8443, "paymail.srv.test")
+            .a("srv.test", Ipv4Addr::new(192, 0, 2, 1)),
+    )
+    .await;
+
+    let endpoint = resolver.resolve("srv.test").await.unwrap();
+    assert_eq!(endpoint.host, "paymail.srv.test");
+    assert_eq!(endpoint.port, 8443);
+    assert_eq!(endpoint.source, EndpointSource::Srv);
+    assert_eq!(endpoint.dnssec_validated, None);
+    assert!(endpoint.ttl.is_some());
+}
+
+#[tokio::test]
+async fn test_a_only_domain_falls_back_to_domain_name() {
+    let resolver =
+        resolver_for(DnsStandIn::new().a("a-only.test", Ipv4Addr::new(192, 0, 2, 1))).await;
+
+    let endpoint = resolver.resolve("a-only.test").await.unwrap();
+    assert_eq!(endpoint.host, "a-only.test");
+    assert_eq!(endpoint.port, 443);
+    assert_eq!(endpoint.source, EndpointSource::AddressFallback);
+}
+
+#[tokio::test]
+async fn test_aaaa_only_domain_falls_back_to_domain_name() {
+    let resolver =
+        resolver_for(DnsStandIn::new().aaaa("aaaa-only.test", Ipv6Addr::LOCALHOST)).await;
+
+    let (host, port) = resolver.resolve_host_port("aaaa-only.test").await.unwrap();
+    assert_eq!(host, "aaaa-only.test");
+    assert_eq!(port, 443);
+}
+
+#[tokio::test]
+async fn test_unknown_domain_fails() {
+    let resolver = resolver_for(DnsStandIn::new()).await;
+
+    let err = resolver
+        .resolve_host_port("missing.test")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+#[tokio::test]
+async fn test_servfail_is_transient() {
+    let resolver = resolver_for(DnsStandIn::new().servfail()).await;
+
+    let err = resolver.resolve("broken.test").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsUnavailable(_)));
+}
+
+#[tokio::test]
+async fn test_unvalidated_out_of_domain_srv_target_is_rejected() {
+    let resolver =
+        resolver_for(DnsStandIn::new().srv("victim.test", 10, 10, 443, "attacker.test")).await;
+
+    let err = resolver.resolve_host_port("victim.test").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+#[tokio::test]
+async fn test_out_of_domain_srv_target_allowed_by_policy() {
+    let addr = DnsStandIn::new()
+        .srv("hosted.test", 10, 10, 443, "provider.test")
+        .start()
+        .await;
+    let (config, opts) = resolver_config(addr);
+    let resolver =
+        DefaultResolver::new(config, opts).with_srv_target_policy(SrvTargetPolicy::Allow);
+
+    let (host, port) = resolver.resolve_host_port("hosted.test").await.unwrap();
+    assert_eq!(host, "provider.test");
+    assert_eq!(port, 443);
+}
+
+#[tokio::test]
+async fn test_foreign_srv_target_is_taken_from_validated_answer() {
+    // The unvalidated answer is spoofed; the validating resolver disagrees.
+    let spoofed = DnsStandIn::new()
+        .srv("victim.test", 10, 10, 443, "attacker.test")
+        .start()
+        .await;
+    let validated = DnsStandIn::new()
+        .srv("victim.test", 10, 10, 8443, "pay.provider.test")
+        .start()
+        .await;
+    let (config, opts) = resolver_config(spoofed);
+    let (validating_config, validating_opts) = resolver_config(validated);
+    let resolver = DefaultResolver::new(config, opts).with_validating_resolver(
+        TokioAsyncResolver::tokio(validating_config, validating_opts),
+    );
+
+    let endpoint = resolver.resolve("victim.test").await.unwrap();
+    assert_eq!(endpoint.host, "pay.provider.test");
+    assert_eq!(endpoint.port, 8443);
+    assert_eq!(
+        endpoint.candidates,
+        vec![("pay.provider.test".to_string(), 8443)]
+    );
+    assert_eq!(endpoint.dnssec_validated, Some(true));
+}
+
+#[tokio::test]
+async fn test_srv_candidates_follow_priority() {
+    let resolver = resolver_for(
+        DnsStandIn::new()
+            .srv("multi.test", 20, 10, 8443, "backup.multi.test")
+            .srv("multi.test", 10, 10, 443, "primary.multi.test"),
+    )
+    .await;
+
+    let endpoint = resolver.resolve("multi.test").await.unwrap();
+    assert_eq!(
+        endpoint.candidates,
+        vec![
+            ("primary.multi.test".to_string(), 443),
+            ("backup.multi.test".to_string(), 8443),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_resolver_reuses_cached_answers() {
+    let (addr, queries) = DnsStandIn::new()
+        .srv("cached.test", 10, 10, 443, "paymail.cached.test")
+        .start_counted()
+        .await;
+    let resolver = DefaultResolver::from_nameservers(&[addr]);
+
+    let first = resolver.resolve_host_port("cached.test").await.unwrap();
+    let sent = queries.load(Ordering::SeqCst);
+    let second = resolver.resolve_host_port("cached.test").await.unwrap();
+
+    assert_eq!(first, ("paymail.cached.test".to_string(), 443));
+    assert_eq!(first, second);
+    assert_eq!(queries.load(Ordering::SeqCst), sent);
+}
+
+#[tokio::test]
+async fn test_static_resolver_parses_hosts_file() {
+    let path = std::env::temp_dir().join(format!("paymail-rs-hosts-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "# staging overrides\n\nexample.com   127.0.0.1:8080\nEXAMPLE.org. paymail.internal # default port\n",
+    )
+    .unwrap();
+    let resolver = StaticResolver::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let (host, port) = resolver.resolve_host_port("example.com").await.unwrap();
+    assert_eq!((host.as_str(), port), ("127.0.0.1", 8080));
+    let (host, port) = resolver.resolve_host_port("example.org").await.unwrap();
+    assert_eq!((host.as_str(), port), ("paymail.internal", 443));
+    let err = resolver.resolve_host_port("example.net").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+#[tokio::test]
+async fn test_static_resolver_parses_ipv6_hosts() {
+    let resolver: StaticResolver =
+        "v6.example [::1]:8443\nbare.example 2001:db8::1\nnoport.example [::1]\n"
+            .parse()
+            .unwrap();
+
+    let (host, port) = resolver.resolve_host_port("v6.example").await.unwrap();
+    assert_eq!((host.as_str(), port), ("::1", 8443));
+    let (host, port) = resolver.resolve_host_port("bare.example").await.unwrap();
+    assert_eq!((host.as_str(), port), ("2001:db8::1", 443));
+    let (host, port) = resolver.resolve_host_port("noport.example").await.unwrap();
+    assert_eq!((host.as_str(), port), ("::1", 443));
+}
+
+#[tokio::test]
+async fn test_static_resolver_matches_idn_domains_by_punycode() {
+    let resolver = StaticResolver::new()
+        .with_host("bücher.example", "127.0.0.1", 8080)
+        .unwrap();
+    let parsed: StaticResolver = "xn--bcher-kva.example 127.0.0.1:8081".parse().unwrap();
+
+    let (host, port) = resolver
+        .resolve_host_port("xn--bcher-kva.example")
+        .await
+        .unwrap();
+    assert_eq!((host.as_str(), port), ("127.0.0.1", 8080));
+    let (_, port) = parsed.resolve_host_port("Bücher.example.").await.unwrap();
+    assert_eq!(port, 8081);
+    let err = StaticResolver::new()
+        .with_host("[::1]", "127.0.0.1", 8080)
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::InvalidFormat(_)));
+}
+
+#[test]
+fn test_static_resolver_rejects_malformed_lines() {
+    for contents in [
+        "example.com",
+        "example.com host:notaport",
+        "a.com b.com c.com",
+        "example.com [::1",
+        "example.com [::1]8443",
+        "127.0.0.1 paymail.internal",
+        "bad..example paymail.internal",
+    ] {
+        let err = contents.parse::<StaticResolver>().unwrap_err();
+        assert!(matches!(err, PaymailError::InvalidFormat(_)), "{contents}");
+    }
+}
+
+#[tokio::test]
+async fn test_chain_resolver_prefers_overrides_and_falls_back() {
+    let dns = resolver_for(DnsStandIn::new().a("public.test", Ipv4Addr::new(192, 0, 2, 1))).await;
+    let overrides = StaticResolver::new()
+        .with_host("public.test", "127.0.0.1", 8080)
+        .unwrap();
+    let overridden = ChainResolver::new(vec![Arc::new(overrides), Arc::new(dns.clone())]);
+    let fallback = ChainResolver::new(vec![Arc::new(StaticResolver::new()), Arc::new(dns)]);
+
+    let (host, port) = overridden.resolve_host_port("public.test").await.unwrap();
+    assert_eq!((host.as_str(), port), ("127.0.0.1", 8080));
+    let (host, port) = fallback.resolve_host_port("public.test").await.unwrap();
+    assert_eq!((host.as_str(), port), ("public.test", 443));
+    let err = fallback
+        .resolve_host_port("missing.test")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+async fn doh_resolver_for(zone: DnsStandIn) -> (MockServer, DohResolver) {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns-query"))
+        .and(header("content-type", "application/dns-message"))
+        .respond_with(zone)
+        .mount(&server)
+        .await;
+    let resolver = DohResolver::new(&format!("{}/dns-query", server.uri()));
+    (server, resolver)
+}
+
+#[tokio::test]
+async fn test_doh_srv_lookup() {
+    let (_server, resolver) = doh_resolver_for(
+        DnsStandIn::new()
+            .srv("doh.test", 20, 10, 8443, "backup.doh.test")
+            .srv("doh.test", 10, 10, 443, "paymail.doh.test"),
+    )
+    .await;
+
+    let endpoint = resolver.resolve("doh.test").await.unwrap();
+    assert_eq!(
+        endpoint.candidates,
+        vec![
+            ("paymail.doh.test".to_string(), 443),
+            ("backup.doh.test".to_string(), 8443),
+        ]
+    );
+    assert_eq!(endpoint.source, EndpointSource::Srv);
+    assert_eq!(endpoint.ttl, Some(Duration::from_secs(300)));
+    // Plain-http endpoints are checked but their AD flag is never trusted.
+    assert_eq!(endpoint.dnssec_validated, Some(false));
+}
+
+#[tokio::test]
+async fn test_doh_falls_back_to_address_records() {
+    let (_server, resolver) =
+        doh_resolver_for(DnsStandIn::new().aaaa("v6.test", Ipv6Addr::LOCALHOST)).await;
+
+    let (host, port) = resolver.resolve_host_port("v6.test").await.unwrap();
+    assert_eq!((host.as_str(), port), ("v6.test", 443));
+    let err = resolver
+        .resolve_host_port("missing.test")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+#[tokio::test]
+async fn test_doh_out_of_domain_srv_target_is_rejected() {
+    let (_server, resolver) =
+        doh_resolver_for(DnsStandIn::new().srv("victim.test", 10, 10, 443, "attacker.test")).await;
+
+    let err = resolver.resolve_host_port("victim.test").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsFailure(_)));
+}
+
+#[tokio::test]
+async fn test_doh_endpoint_errors_are_transient() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    let resolver = DohResolver::new(&format!("{}/dns-query", server.uri()));
+
+    let err = resolver.resolve_host_port("doh.test").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsUnavailable(_)));
+
+    let (_server, resolver) = doh_resolver_for(DnsStandIn::new().servfail()).await;
+    let err = resolver.resolve_host_port("doh.test").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsUnavailable(_)));
+}
+
+#[tokio::test]
+async fn test_chain_resolver_skips_unreachable_doh_endpoint() {
+    // Bind and drop a listener so the port refuses connections.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let doh = DohResolver::new(&format!("http://{addr}/dns-query"));
+    let overrides = StaticResolver::new()
+        .with_host("example.com", "127.0.0.1", 8080)
+        .unwrap();
+
+    let err = doh.resolve("example.com").await.unwrap_err();
+    assert!(matches!(err, PaymailError::DnsUnavailable(_)), "{err:?}");
+    let chain = ChainResolver::new(vec![Arc::new(doh), Arc::new(overrides)]);
+    let (host, port) = chain.resolve_host_port("example.com").await.unwrap();
+    assert_eq!((host.as_str(), port), ("127.0.0.1", 8080));
+}
+
+struct LegacyResolver;
+
+#[async_trait::async_trait]
+impl HostResolver for LegacyResolver {
+    async fn resolve_host(&self, _domain: &str) -> Result<(String, u16), PaymailError> {
+        Ok(("legacy.test".to_string(), 8443))
+    }
+}
+
+#[tokio::test]
+async fn test_resolve_host_only_implementors_still_resolve() {
+    let endpoint = LegacyResolver.resolve("example.com").await.unwrap();
+
+    assert_eq!(endpoint.source, EndpointSource::Unknown);
+    assert_eq!(endpoint.candidates, vec![("legacy.test".to_string(), 8443)]);
+    assert_eq!(endpoint.ttl, None);
+    assert_eq!(endpoint.dnssec_validated, None);
+    // Both traits are in scope; neither call is ambiguous.
+    assert_eq!(
+        LegacyResolver.resolve_host("example.com").await.unwrap(),
+        LegacyResolver
+            .resolve_host_port("example.com")
+            .await
+            .unwrap()
+    );
+}
@@ -0,0 +1,153 @@
+use paymail_rs::errors::PaymailError;
+use paymail_rs::models::PaymentRequest;
+use paymail_rs::utils::{
+    Network, SignatureScheme, generate_signature, generate_signature_with, p2pkh_address, txid,
+    verify_signature, verify_signature_for_address, verify_signature_with,
+};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+const MESSAGE: &str = "This is an example of a signed message.";
+// Reference signatures from bitcoinjs-message for the key sha256("").
+const COMPRESSED_SIG: &str =
+    "H9L5yLFjti0QTHhPyFrZCT1V/MMnBtXKmoiKDZ78NDBjERki6ZTQZdSMCtkgoNmp17By9ItJr8o7ChX0XxY91nk=";
+const UNCOMPRESSED_SIG: &str =
+    "G9L5yLFjti0QTHhPyFrZCT1V/MMnBtXKmoiKDZ78NDBjERki6ZTQZdSMCtkgoNmp17By9ItJr8o7ChX0XxY91nk=";
+
+fn test_key() -> (SecretKey, PublicKey) {
+    let secret = SecretKey::from_byte_array([
+        0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9,
+        0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52,
+        0xb8, 0x55,
+    ])
+    .unwrap();
+    let public = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+    (secret, public)
+}
+
+#[test]
+fn test_bsm_signature_matches_reference_wallets() {
+    let (secret, public) = test_key();
+
+    let signature = generate_signature(&secret, MESSAGE).unwrap();
+    assert_eq!(signature, COMPRESSED_SIG);
+    assert!(verify_signature(&public.to_string(), COMPRESSED_SIG, MESSAGE).unwrap());
+}
+
+#[test]
+fn test_bsm_accepts_uncompressed_header() {
+    let (_, public) = test_key();
+    let uncompressed = hex::encode(public.serialize_uncompressed());
+
+    assert!(verify_signature(&uncompressed, UNCOMPRESSED_SIG, MESSAGE).unwrap());
+}
+
+#[test]
+fn test_bsm_rejects_other_message_and_key() {
+    let (_, public) = test_key();
+    let other = PublicKey::from_secret_key(
+        &Secp256k1::new(),
+        &SecretKey::from_byte_array([0x01; 32]).unwrap(),
+    );
+
+    assert!(!verify_signature(&public.to_string(), COMPRESSED_SIG, "tampered").unwrap());
+    assert!(!verify_signature(&other.to_string(), COMPRESSED_SIG, MESSAGE).unwrap());
+}
+
+#[test]
+fn test_legacy_scheme_is_explicit_and_not_interchangeable() {
+    let (secret, public) = test_key();
+    let pubkey = public.to_string();
+
+    let legacy = generate_signature_with(&secret, MESSAGE, SignatureScheme::Legacy).unwrap();
+    assert!(verify_signature_with(&pubkey, &legacy, MESSAGE, SignatureScheme::Legacy).unwrap());
+    assert!(!verify_signature(&pubkey, &legacy, MESSAGE).unwrap());
+    let err = verify_signature_with(&pubkey, UNCOMPRESSED_SIG, MESSAGE, SignatureScheme::Legacy)
+        .unwrap_err();
+    assert!(matches!(err, PaymailError::InvalidSignature(_)));
+}
+
+#[test]
+fn test_verify_against_mainnet_and_testnet_addresses() {
+    let (_, public) = test_key();
+    let mainnet = p2pkh_address(&public, true, Network::Mainnet);
+    let testnet = p2pkh_address(&public, true, Network::Testnet);
+    assert_eq!(mainnet, "1F3sAm6ZtwLAUnj7d38pGFxtP3RVEvtsbV");
+    assert!(testnet.starts_with('m') || testnet.starts_with('n'));
+
+    for address in [&mainnet, &testnet] {
+        let recovered = verify_signature_for_address(address, COMPRESSED_SIG, MESSAGE).unwrap();
+        assert_eq!(recovered, Some(public));
+    }
+    // The uncompressed header commits to the uncompressed key's address.
+    assert_eq!(
+        verify_signature_for_address(&mainnet, UNCOMPRESSED_SIG, MESSAGE).unwrap(),
+        None
+    );
+    let uncompressed = p2pkh_address(&public, false, Network::Mainnet);
+    assert_eq!(
+        verify_signature_for_address(&uncompressed, UNCOMPRESSED_SIG, MESSAGE).unwrap(),
+        Some(public)
+    );
+}
+
+#[test]
+fn test_address_mismatch_and_malformed_address() {
+    let (_, public) = test_key();
+    let address = p2pkh_address(&public, true, Network::Mainnet);
+
+    assert_eq!(
+        verify_signature_for_address(&address, COMPRESSED_SIG, "tampered").unwrap(),
+        None
+    );
+    let err = verify_signature_for_address("1NotAnAddress", COMPRESSED_SIG, MESSAGE).unwrap_err();
+    assert!(matches!(err, PaymailError::InvalidFormat(_)));
+}
+
+#[test]
+fn test_payment_request_signs_the_spec_message() {
+    let (secret, public) = test_key();
+    let mut request = PaymentRequest {
+        sender_name: Some("Alice".to_string()),
+        sender_handle: "alice@example.com".to_string(),
+        dt: "2024-01-01T00:00:00.000Z".to_string(),
+        amount: Some(550),
+        purpose: Some("Coffee".to_string()),
+        signature: String::new(),
+    };
+    // Deterministic BSM signatures over the reference clients' message, from
+    // an RFC 6979 signer that reproduces the bitcoinjs-message vectors above.
+    let cases = [
+        (
+            "alice@example.com5502024-01-01T00:00:00.000ZCoffee",
+            "IIz2MMgo5CJywAEBDoduJ2vZ8fLJr9qHWEnbdXvFDhCoZ+tsf1j9qiwq2txaTLxFea1rPQRoLfKS3WJmPFTc2xA=",
+        ),
+        (
+            "alice@example.com02024-01-01T00:00:00.000Z",
+            "ILv2i3bHEz6ZoJMYeSS3yxYYqsH36B8OUAJCO5ojJyRvUe2h8/YDyNVw9wd5PjsA734A0KPDL9IMcs1GT9wAGwY=",
+        ),
+    ];
+
+    for (message, signature) in cases {
+        assert_eq!(request.signable_message(), message);
+        assert_eq!(generate_signature(&secret, message).unwrap(), signature);
+        assert!(verify_signature(&public.to_string(), signature, message).unwrap());
+        request.amount = None;
+        request.purpose = None;
+    }
+}
+
+#[test]
+fn test_txid_of_genesis_coinbase() {
+    let genesis = concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04",
+        "ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e",
+        "206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f205",
+        "2a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6",
+        "bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+    );
+    assert_eq!(
+        txid(genesis).unwrap(),
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+    );
+    assert!(matches!(txid("zz"), Err(PaymailError::InvalidFormat(_))));
+}